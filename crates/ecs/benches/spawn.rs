@@ -0,0 +1,58 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use ecs::World;
+use ecs_derive::Component;
+
+// The component data is only written, the benchmark measures spawning alone.
+
+#[allow(dead_code)]
+#[derive(Debug, Component)]
+struct Position(f32, f32);
+
+#[allow(dead_code)]
+#[derive(Debug, Component)]
+struct Velocity(f32, f32);
+
+#[allow(dead_code)]
+#[derive(Debug, Default, Component)]
+struct Mass(f32);
+
+#[derive(Debug, Component)]
+#[component(requires(Mass))]
+struct Body;
+
+fn spawn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn");
+    for count in [100, 1_000, 10_000] {
+        group.bench_with_input(BenchmarkId::new("loop", count), &count, |b, &count| {
+            b.iter_batched(
+                World::new,
+                |world| {
+                    for i in 0..count {
+                        let entity =
+                            world.spawn((Position(i as f32, 0.0), Velocity(1.0, 0.0), Body));
+                        black_box(entity.id());
+                    }
+                    world
+                },
+                BatchSize::LargeInput,
+            );
+        });
+
+        group.bench_with_input(BenchmarkId::new("batch", count), &count, |b, &count| {
+            b.iter_batched(
+                World::new,
+                |world| {
+                    let bundles =
+                        (0..count).map(|i| (Position(i as f32, 0.0), Velocity(1.0, 0.0), Body));
+                    black_box(world.spawn_batch(bundles));
+                    world
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, spawn);
+criterion_main!(benches);
@@ -0,0 +1,242 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll},
+};
+
+use futures::task::noop_waker_ref;
+use parking_lot::Mutex;
+
+use crate::{
+    scheduler::SystemDescriptor,
+    sealed::{self, Sealer},
+    Fallible, PinnedFut, System, SystemParam, SystemParams, SystemResult, World,
+};
+
+/// Gives a background system access to its parameters, see [`BackgroundSystem`].
+///
+/// The parameters are fetched anew on every call to [`access`](Self::access) and only lent to the closure,
+/// so they are released when it returns and a background system never holds any locks while it is suspended.
+///
+/// ```ignore
+/// async fn upload_scores(bg: Background<Query<&Score>>) {
+///     loop {
+///         let scores: Vec<Score> = bg.access(|query| query.into_iter().copied().collect());
+///         upload(scores).await;
+///         bg.next_tick().await;
+///     }
+/// }
+///
+/// schedule.add_background_system(upload_scores);
+/// ```
+pub struct Background<P: FetchParams> {
+    shared: Arc<BackgroundShared<P>>,
+}
+
+struct BackgroundShared<P: FetchParams> {
+    /// Weak to prevent a reference cycle, since the system's future is owned by the world's schedules.
+    world: Weak<World>,
+    state: P::ArcState,
+    /// Whether the future is currently being polled by its schedule.
+    polling: AtomicBool,
+}
+
+impl<P: FetchParams> Background<P> {
+    /// Fetches the parameters and lends them to `f`, releasing them again afterwards.
+    ///
+    /// The parameters are only borrowed for the duration of the call, so neither they nor anything
+    /// borrowed from them can be returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of the future of the background system itself, for example from a spawned task.
+    pub fn access<T>(&self, f: impl FnOnce(&mut P) -> T) -> T {
+        assert!(
+            self.shared.polling.load(Ordering::SeqCst),
+            "Background parameters can only be accessed while the schedule polls the system."
+        );
+
+        let world = self
+            .shared
+            .world
+            .upgrade()
+            .expect("Cannot access parameters, the world has been dropped.");
+
+        let mut params = P::fetch::<Sealer>(&world, &self.shared.state);
+        f(&mut params)
+    }
+
+    /// Suspends the system until the next time its schedule runs.
+    pub fn next_tick(&self) -> NextTick {
+        NextTick { yielded: false }
+    }
+}
+
+impl<P: FetchParams> Clone for Background<P> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Completes the next time the background system is polled, see [`Background::next_tick`].
+pub struct NextTick {
+    yielded: bool,
+}
+
+impl Future for NextTick {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        // Background systems are polled once per run, so there is no need to wake them.
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            Poll::Pending
+        }
+    }
+}
+
+/// Parameters that can be fetched outside of a regular system call, see [`Background`].
+///
+/// This is implemented for system parameters and tuples of them. Unlike for regular systems, the parameters
+/// may borrow with elided lifetimes, such as `Background<Query<&Score>>` in the signature of an async fn.
+pub trait FetchParams: SystemParams + Send + Sync + Sized {
+    #[doc(hidden)]
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, state: &Self::ArcState) -> Self;
+}
+
+impl<P: SystemParam> FetchParams for P {
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, state: &Self::ArcState) -> Self {
+        P::fetch::<S>(world, state)
+    }
+}
+
+impl<P1, P2> FetchParams for (P1, P2)
+where
+    P1: SystemParam,
+    P2: SystemParam,
+{
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, state: &Self::ArcState) -> Self {
+        (
+            P1::fetch::<S>(world, &state.0),
+            P2::fetch::<S>(world, &state.1),
+        )
+    }
+}
+
+impl<P1, P2, P3> FetchParams for (P1, P2, P3)
+where
+    P1: SystemParam,
+    P2: SystemParam,
+    P3: SystemParam,
+{
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, state: &Self::ArcState) -> Self {
+        (
+            P1::fetch::<S>(world, &state.0),
+            P2::fetch::<S>(world, &state.1),
+            P3::fetch::<S>(world, &state.2),
+        )
+    }
+}
+
+type BackgroundFut = Pin<Box<dyn Future<Output = SystemResult> + Send>>;
+
+/// An async system whose future keeps running across ticks without stalling its schedule.
+///
+/// Every time the schedule runs, the future is polled once in the system's slot. When it has completed,
+/// the next run starts a new one. Parameters are only accessed through [`Background::access`], so the schedule
+/// accounts for them like for any other system while no locks are held across ticks.
+///
+/// Created by [`Schedule::add_background_system`](crate::Schedule::add_background_system).
+pub struct BackgroundSystem<P: FetchParams> {
+    id: usize,
+    name: &'static str,
+    system: Box<dyn Fn(Background<P>) -> BackgroundFut + Send + Sync>,
+    shared: Arc<BackgroundShared<P>>,
+    /// The future that is currently in flight.
+    running: Mutex<Option<BackgroundFut>>,
+}
+
+impl<P: FetchParams> BackgroundSystem<P> {
+    pub(crate) fn new<F, Fut>(id: usize, world: &Arc<World>, system: F) -> Self
+    where
+        F: Fn(Background<P>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Fallible,
+    {
+        Self {
+            id,
+            name: std::any::type_name::<F>(),
+            system: Box::new(move |bg| {
+                let fut = system(bg);
+                Box::pin(async move { fut.await.into_result() })
+            }),
+            shared: Arc::new(BackgroundShared {
+                world: Arc::downgrade(world),
+                state: P::state(world),
+                polling: AtomicBool::new(false),
+            }),
+            running: Mutex::new(None),
+        }
+    }
+}
+
+unsafe impl<P: FetchParams> System for BackgroundSystem<P> {
+    fn descriptor(&self) -> SystemDescriptor {
+        SystemDescriptor {
+            id: self.id,
+            deps: P::descriptors(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn call(&self, _world: &Arc<World>) -> PinnedFut {
+        let mut running = self.running.lock();
+        let fut = running.get_or_insert_with(|| {
+            (self.system)(Background {
+                shared: Arc::clone(&self.shared),
+            })
+        });
+
+        self.shared.polling.store(true, Ordering::SeqCst);
+        let poll = fut
+            .as_mut()
+            .poll(&mut Context::from_waker(noop_waker_ref()));
+        self.shared.polling.store(false, Ordering::SeqCst);
+
+        let result = match poll {
+            Poll::Ready(result) => {
+                *running = None;
+                result
+            }
+            Poll::Pending => Ok(()),
+        };
+
+        Box::pin(std::future::ready(result))
+    }
+
+    /// The future is only polled, never awaited, so the system never blocks its schedule.
+    fn is_async(&self) -> bool {
+        false
+    }
+
+    fn init(&self, world: &Arc<World>) {
+        P::init(world, &self.shared.state);
+    }
+
+    fn destroy(&self, world: &Arc<World>) {
+        // Cancel the future before tearing down the parameters it uses.
+        self.running.lock().take();
+        P::destroy(world, &self.shared.state);
+    }
+}
@@ -1,31 +1,142 @@
 use crate::entity::EntityId;
-use crate::{EcsResult, PersistentLock};
-use dashmap::DashMap;
+use crate::{
+    DeferredWorld, EcsError, EcsResult, LockGuard, PersistentLock, ReadLock, World, WriteLock,
+};
+use dashmap::{DashMap, SharedValue};
 use parking_lot::RwLock;
+use smallvec::{smallvec, SmallVec};
 use std::any::{Any, TypeId};
 use std::cell::UnsafeCell;
-use std::usize;
+use std::ops::{Deref, DerefMut};
+use std::sync::Weak;
+
+/// Data that can be attached to an entity.
+///
+/// This is usually implemented with `#[derive(Component)]`. Components can declare other components
+/// that every entity with this component must also have:
+///
+/// ```ignore
+/// #[derive(Component)]
+/// #[component(requires(Health, Position = Position::new(0.0, 0.0)))]
+/// struct Player;
+/// ```
+///
+/// The initial value is either an expression evaluated on every insertion or a path to a function
+/// or a closure constructing the component, like `Position = Position::origin`.
+/// Required components without an initial value are created with [`Default`]. They are inserted whenever
+/// the component is spawned or inserted and the entity does not have them yet, including the requirements
+/// of the required components themselves.
+pub trait Component: Send + Sync + 'static {
+    /// Inserts the components required by this component that the entity does not have yet.
+    fn insert_required(_components: &Components, _entity: EntityId) -> EcsResult<()> {
+        Ok(())
+    }
+
+    /// Inserts the components required by this component for a batch of freshly spawned entities.
+    ///
+    /// The default implementation calls [`insert_required`](Self::insert_required) for every entity.
+    /// The derive macro overrides this to lock and grow every required storage only once.
+    fn insert_required_batch(components: &Components, entities: &[EntityId]) -> EcsResult<()> {
+        for entity in entities {
+            Self::insert_required(components, *entity)?;
+        }
 
-pub trait Component: Send + Sync + 'static {}
+        Ok(())
+    }
+
+    /// Registers the lifecycle hooks declared for this component.
+    /// This is called once when the storage for this component is created.
+    fn register_hooks(_hooks: &mut ComponentHooks) {}
+}
 
 pub trait SpawnBundle {
+    /// Inserts all components of the bundle into storage.
+    ///
+    /// Implementations can return early on the first error, [`World::try_spawn`](crate::World::try_spawn)
+    /// takes care of removing any components that were already inserted.
     fn insert_into(self, components: &Components, entity: EntityId) -> EcsResult<()>;
+
+    /// The type IDs of all components in this bundle.
+    fn type_ids() -> SmallVec<[TypeId; 3]>
+    where
+        Self: Sized;
+
+    /// Returns [`EcsError::DuplicateComponent`] if the bundle contains a component type more than once.
+    ///
+    /// Such a bundle would insert the same component twice, so spawning rejects it up front.
+    fn validate() -> EcsResult<()>
+    where
+        Self: Sized,
+    {
+        let type_ids = Self::type_ids();
+        for (i, type_id) in type_ids.iter().enumerate() {
+            if type_ids[..i].contains(type_id) {
+                return Err(EcsError::DuplicateComponent(std::any::type_name::<Self>()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a batch of bundles into storage, where `bundles[i]` belongs to `entities[i]`.
+    ///
+    /// The default implementation inserts the bundles one by one. Implementations should override this
+    /// to lock and grow every storage only once for the entire batch.
+    fn insert_batch(
+        bundles: Vec<Self>,
+        components: &Components,
+        entities: &[EntityId],
+    ) -> EcsResult<()>
+    where
+        Self: Sized,
+    {
+        for (bundle, entity) in bundles.into_iter().zip(entities) {
+            bundle.insert_into(components, *entity)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl SpawnBundle for () {
     fn insert_into(self, _components: &Components, _entity: EntityId) -> EcsResult<()> {
         Ok(())
     }
+
+    fn type_ids() -> SmallVec<[TypeId; 3]> {
+        SmallVec::new()
+    }
+
+    fn insert_batch(
+        _bundles: Vec<Self>,
+        _components: &Components,
+        _entities: &[EntityId],
+    ) -> EcsResult<()> {
+        Ok(())
+    }
 }
 
-impl<'a, C0: Component + 'static> SpawnBundle for C0 {
+impl<C0: Component + 'static> SpawnBundle for C0 {
     fn insert_into(self, components: &Components, entity: EntityId) -> EcsResult<()> {
         components.insert(entity, self)?;
-        Ok(())
+        C0::insert_required(components, entity)
+    }
+
+    fn type_ids() -> SmallVec<[TypeId; 3]> {
+        smallvec![TypeId::of::<C0>()]
+    }
+
+    fn insert_batch(
+        bundles: Vec<Self>,
+        components: &Components,
+        entities: &[EntityId],
+    ) -> EcsResult<()> {
+        components.extend(entities, bundles)?;
+        C0::insert_required_batch(components, entities)
     }
 }
 
-impl<'a, C0, C1> SpawnBundle for (C0, C1)
+impl<C0, C1> SpawnBundle for (C0, C1)
 where
     C0: Component + 'static,
     C1: Component + 'static,
@@ -34,17 +145,91 @@ where
         components.insert(entity, self.0)?;
         components.insert(entity, self.1)?;
 
-        Ok(())
+        C0::insert_required(components, entity)?;
+        C1::insert_required(components, entity)
+    }
+
+    fn type_ids() -> SmallVec<[TypeId; 3]> {
+        smallvec![TypeId::of::<C0>(), TypeId::of::<C1>()]
+    }
+
+    fn insert_batch(
+        bundles: Vec<Self>,
+        components: &Components,
+        entities: &[EntityId],
+    ) -> EcsResult<()> {
+        let (c0, c1): (Vec<C0>, Vec<C1>) = bundles.into_iter().unzip();
+        components.extend(entities, c0)?;
+        components.extend(entities, c1)?;
+
+        C0::insert_required_batch(components, entities)?;
+        C1::insert_required_batch(components, entities)
+    }
+}
+
+impl<C0, C1, C2> SpawnBundle for (C0, C1, C2)
+where
+    C0: Component + 'static,
+    C1: Component + 'static,
+    C2: Component + 'static,
+{
+    fn insert_into(self, components: &Components, entity: EntityId) -> EcsResult<()> {
+        components.insert(entity, self.0)?;
+        components.insert(entity, self.1)?;
+        components.insert(entity, self.2)?;
+
+        C0::insert_required(components, entity)?;
+        C1::insert_required(components, entity)?;
+        C2::insert_required(components, entity)
+    }
+
+    fn type_ids() -> SmallVec<[TypeId; 3]> {
+        smallvec![TypeId::of::<C0>(), TypeId::of::<C1>(), TypeId::of::<C2>()]
+    }
+
+    fn insert_batch(
+        bundles: Vec<Self>,
+        components: &Components,
+        entities: &[EntityId],
+    ) -> EcsResult<()> {
+        let mut c0 = Vec::with_capacity(bundles.len());
+        let mut c1 = Vec::with_capacity(bundles.len());
+        let mut c2 = Vec::with_capacity(bundles.len());
+        for (b0, b1, b2) in bundles {
+            c0.push(b0);
+            c1.push(b1);
+            c2.push(b2);
+        }
+
+        components.extend(entities, c0)?;
+        components.extend(entities, c1)?;
+        components.extend(entities, c2)?;
+
+        C0::insert_required_batch(components, entities)?;
+        C1::insert_required_batch(components, entities)?;
+        C2::insert_required_batch(components, entities)
     }
 }
 
 pub trait TypelessStorage: Send + Sync {
     /// Casts the storage to `Any`.
     fn as_any(&self) -> &dyn Any;
-    /// Removes the entity from the storage. Returns `true` if the storage is now empty.
+    /// Removes the entity from the storage. Returns `true` if the entity had a component in this storage.
+    ///
+    /// This function returns an error if the component storage is currently locked.
     fn remove(&self, entity: EntityId) -> EcsResult<bool>;
     /// Returns whether the storage contains the given entity.
     fn has_entity(&self, entity: EntityId) -> bool;
+    /// Returns the lifecycle hooks registered for this component type.
+    fn hooks(&self) -> ComponentHooks;
+    /// Returns the lock guarding this storage.
+    fn lock(&self) -> &PersistentLock;
+    /// Drops all components in the storage while keeping the storage itself and its hooks.
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold the write lock of this storage.
+    unsafe fn clear(&self);
 }
 
 pub struct TypedStorage<T> {
@@ -53,6 +238,8 @@ pub struct TypedStorage<T> {
 
     pub(crate) lock: PersistentLock,
     pub(crate) storage: UnsafeCell<Vec<T>>,
+
+    pub(crate) hooks: RwLock<ComponentHooks>,
 }
 
 unsafe impl<T: Send + Sync + 'static> Send for TypedStorage<T> {}
@@ -67,6 +254,8 @@ impl<T: Send + Sync + 'static> TypedStorage<T> {
 
             lock: PersistentLock::new(),
             storage: UnsafeCell::new(vec![component]),
+
+            hooks: RwLock::new(ComponentHooks::default()),
         })
     }
 
@@ -90,23 +279,86 @@ impl<T: Send + Sync + 'static> TypedStorage<T> {
         } else {
             // Entity does not have a component of this type yet.
 
-            let index;
-            {
-                let _guard = self.lock.write()?;
-                // Safety: Acquiring a mutable reference to the vec is safe because the
-                // `acquire_write` call above ensures exclusive access.
-                let storage = unsafe { &mut *self.storage.get() };
-
-                index = storage.len();
-                storage.push(component);
-            }
+            let _guard = self.lock.write()?;
+            // Safety: Acquiring a mutable reference to the vec is safe because the
+            // `acquire_write` call above ensures exclusive access.
+            let storage = unsafe { &mut *self.storage.get() };
 
-            self.map.insert(entity, index);
+            // Update the mappings while still holding the lock so that readers never observe
+            // an index that does not exist yet.
+            self.map.insert(entity, storage.len());
             self.reverse_map.write().push(entity);
+            storage.push(component);
 
             Ok(None)
         }
     }
+
+    /// Inserts components for a batch of entities that do not have a component of this type yet,
+    /// where `components[i]` belongs to `entities[i]`. The entities must be distinct.
+    ///
+    /// Unlike [`insert`](Self::insert), this locks and grows the storage only once, and every shard
+    /// of the index is locked and grown only once as well.
+    /// This function returns an error if the component storage is currently locked.
+    pub fn extend(&self, entities: &[EntityId], components: Vec<T>) -> EcsResult<()> {
+        debug_assert_eq!(entities.len(), components.len());
+
+        let _guard = self.lock.write()?;
+        // Safety: Acquiring a mutable reference to the vec is safe because the
+        // `acquire_write` call above ensures exclusive access.
+        let storage = unsafe { &mut *self.storage.get() };
+
+        let shards = self.map.shards();
+        let mut per_shard = vec![Vec::new(); shards.len()];
+        for (index, entity) in (storage.len()..).zip(entities) {
+            per_shard[self.map.determine_map(entity)].push((*entity, index));
+        }
+
+        for (shard, entries) in shards.iter().zip(per_shard) {
+            if entries.is_empty() {
+                continue;
+            }
+
+            let mut shard = shard.write();
+            shard.reserve(entries.len());
+            for (entity, index) in entries {
+                shard.insert(entity, SharedValue::new(index));
+            }
+        }
+
+        self.reverse_map.write().extend_from_slice(entities);
+        storage.extend(components);
+
+        Ok(())
+    }
+
+    /// Removes the component of the given entity, returning it if the entity had one.
+    ///
+    /// This function returns an error if the component storage is currently locked.
+    pub fn take(&self, entity: EntityId) -> EcsResult<Option<T>> {
+        if !self.map.contains_key(&entity) {
+            return Ok(None);
+        }
+
+        let _guard = self.lock.write()?;
+        let Some((_, index)) = self.map.remove(&entity) else {
+            return Ok(None);
+        };
+
+        // Safety: Acquiring a mutable reference to the vec is safe because the
+        // `acquire_write` call above ensures exclusive access.
+        let storage = unsafe { &mut *self.storage.get() };
+        let component = storage.swap_remove(index);
+
+        // The last component has been moved into the freed slot, update its mapping.
+        let mut reverse_lock = self.reverse_map.write();
+        reverse_lock.swap_remove(index);
+        if let Some(moved) = reverse_lock.get(index) {
+            self.map.insert(*moved, index);
+        }
+
+        Ok(Some(component))
+    }
 }
 
 impl<T> Default for TypedStorage<T> {
@@ -116,6 +368,8 @@ impl<T> Default for TypedStorage<T> {
             reverse_map: RwLock::new(Vec::new()),
             lock: PersistentLock::new(),
             storage: UnsafeCell::new(Vec::new()),
+
+            hooks: RwLock::new(ComponentHooks::default()),
         }
     }
 }
@@ -126,63 +380,384 @@ impl<T: Send + Sync + 'static> TypelessStorage for TypedStorage<T> {
     }
 
     fn remove(&self, entity: EntityId) -> EcsResult<bool> {
-        if let Some((_, index)) = self.map.remove(&entity) {
-            let is_empty;
-            {
-                let _guard = self.lock.write()?;
-                // Safety: Acquiring a mutable reference to the vec is safe because the
-                // `acquire_write` call above ensures exclusive access.
-                let storage = unsafe { &mut *self.storage.get() };
+        Ok(self.take(entity)?.is_some())
+    }
 
-                storage.swap_remove(index);
-                is_empty = storage.is_empty();
-            }
+    fn has_entity(&self, entity: EntityId) -> bool {
+        self.map.contains_key(&entity)
+    }
 
-            if is_empty {
-                self.reverse_map.write().clear();
-                return Ok(false);
-            }
+    fn hooks(&self) -> ComponentHooks {
+        *self.hooks.read()
+    }
 
-            // Modify mapping for affected tail entity.
-            let mut reverse_lock = self.reverse_map.write();
-            let modified_id = reverse_lock[reverse_lock.len() - 1];
-            self.map.insert(modified_id, index);
-            reverse_lock.swap_remove(index);
+    fn lock(&self) -> &PersistentLock {
+        &self.lock
+    }
 
-            Ok(true)
-        } else {
-            let _guard = self.lock.read()?;
-            // Safety: Acquiring a mutable reference to the vec is safe because the
-            // `acquire_write` call above ensures non-mutable access.
-            let storage = unsafe { &*self.storage.get() };
+    unsafe fn clear(&self) {
+        self.map.clear();
+        self.reverse_map.write().clear();
 
-            Ok(storage.is_empty())
-        }
+        // Safety: The caller guarantees exclusive access to the storage.
+        unsafe { (*self.storage.get()).clear() };
     }
+}
 
-    fn has_entity(&self, entity: EntityId) -> bool {
-        self.map.contains_key(&entity)
+/// A shared reference to a component, obtained outside of a system.
+///
+/// The component storage stays read-locked for as long as this reference exists.
+/// Systems requesting exclusive access to the storage will fail to lock it in the meantime.
+pub struct ComponentRef<'a, T: Component> {
+    _guard: LockGuard<'a, ReadLock>,
+    component: &'a T,
+}
+
+impl<'a, T: Component> Deref for ComponentRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.component
+    }
+}
+
+/// A unique reference to a component, obtained outside of a system.
+///
+/// The component storage stays write-locked for as long as this reference exists.
+/// Any other attempt to access the storage will fail in the meantime.
+pub struct ComponentMut<'a, T: Component> {
+    _guard: LockGuard<'a, WriteLock>,
+    component: &'a mut T,
+}
+
+impl<'a, T: Component> Deref for ComponentMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.component
+    }
+}
+
+impl<'a, T: Component> DerefMut for ComponentMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.component
+    }
+}
+
+/// A hook that is called when a component is added to or removed from an entity.
+///
+/// Hooks receive a [`DeferredWorld`] that can read components and resources but defers
+/// all structural changes to the end of the tick.
+pub type ComponentHook = fn(DeferredWorld, EntityId);
+
+/// The lifecycle hooks registered for a component type.
+///
+/// Hooks can be registered with [`World::register_hooks`](crate::World::register_hooks)
+/// or through the derive macro:
+///
+/// ```ignore
+/// #[derive(Component)]
+/// #[component(on_add = index_xuid, on_remove = unindex_xuid)]
+/// struct Xuid(u64);
+/// ```
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ComponentHooks {
+    pub(crate) on_add: Option<ComponentHook>,
+    pub(crate) on_insert: Option<ComponentHook>,
+    pub(crate) on_replace: Option<ComponentHook>,
+    pub(crate) on_remove: Option<ComponentHook>,
+}
+
+impl ComponentHooks {
+    /// Called after the component has been added to an entity that did not have it yet.
+    pub fn on_add(&mut self, hook: ComponentHook) -> &mut Self {
+        self.on_add = Some(hook);
+        self
+    }
+
+    /// Called after the component has been inserted, both when it is added and when it replaces an old value.
+    pub fn on_insert(&mut self, hook: ComponentHook) -> &mut Self {
+        self.on_insert = Some(hook);
+        self
+    }
+
+    /// Called before the component is replaced by a new value or removed.
+    /// The old value can still be accessed at this point.
+    pub fn on_replace(&mut self, hook: ComponentHook) -> &mut Self {
+        self.on_replace = Some(hook);
+        self
+    }
+
+    /// Called before the component is removed from an entity, including when the entity is despawned.
+    /// The old value can still be accessed at this point.
+    pub fn on_remove(&mut self, hook: ComponentHook) -> &mut Self {
+        self.on_remove = Some(hook);
+        self
     }
 }
 
+/// Returns [`EcsError::StorageLocked`] if the storage is currently borrowed.
+///
+/// Hooks run before the storage is write-locked so that they can still read the component.
+/// Checking the lock first ensures that they only run when the mutation that follows will succeed.
+fn ensure_unlocked(store: &dyn TypelessStorage) -> EcsResult<()> {
+    store.lock().write().map(drop)
+}
+
 #[derive(Default)]
 pub struct Components {
     pub(crate) map: DashMap<TypeId, Box<dyn TypelessStorage>>,
+    /// The world these components belong to, used to give hooks access to the world.
+    pub(crate) world: Weak<World>,
 }
 
 impl Components {
     pub fn insert<T: Component>(&self, entity: EntityId, component: T) -> EcsResult<Option<T>> {
-        let type_id = TypeId::of::<T>();
+        let typed = self.typed_or_default::<T>();
+        let hooks = typed.hooks();
 
-        if let Some(store) = self.map.get_mut(&type_id) {
-            let downcast: &TypedStorage<T> = store.as_any().downcast_ref().unwrap();
-            downcast.insert(entity, component)
-        } else {
-            self.map
-                .insert(type_id, TypedStorage::with(entity, component));
+        if typed.has_entity(entity) {
+            ensure_unlocked(typed)?;
+            self.run_hook(hooks.on_replace, entity);
+        }
 
-            Ok(None)
+        let replaced = typed.insert(entity, component)?;
+        if replaced.is_none() {
+            self.run_hook(hooks.on_add, entity);
+        }
+        self.run_hook(hooks.on_insert, entity);
+
+        Ok(replaced)
+    }
+
+    /// Inserts the component `T` created by `ctor` if the entity does not have it yet,
+    /// followed by the components required by `T`.
+    ///
+    /// This is used by the implementations of [`Component::insert_required`] generated by the derive macro.
+    #[doc(hidden)]
+    pub fn insert_if_missing<T, F>(&self, entity: EntityId, ctor: F) -> EcsResult<()>
+    where
+        T: Component,
+        F: FnOnce() -> T,
+    {
+        if self.has_component::<T>(entity) {
+            return Ok(());
+        }
+
+        self.insert(entity, ctor())?;
+        T::insert_required(self, entity)
+    }
+
+    /// Inserts the component `T` created by `ctor` into every entity in the batch that does not have it yet,
+    /// followed by the components required by `T`.
+    ///
+    /// This is the batched counterpart of [`insert_if_missing`](Self::insert_if_missing), used by the
+    /// implementations of [`Component::insert_required_batch`] generated by the derive macro.
+    #[doc(hidden)]
+    pub fn insert_batch_if_missing<T, F>(&self, entities: &[EntityId], ctor: F) -> EcsResult<()>
+    where
+        T: Component,
+        F: Fn() -> T,
+    {
+        let missing: Vec<EntityId> = entities
+            .iter()
+            .copied()
+            .filter(|entity| !self.has_component::<T>(*entity))
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let components = missing.iter().map(|_| ctor()).collect();
+        self.extend(&missing, components)?;
+        T::insert_required_batch(self, &missing)
+    }
+
+    /// Inserts components for a batch of freshly spawned entities, where `components[i]` belongs to `entities[i]`.
+    ///
+    /// This locks and grows the storage only once, see [`TypedStorage::extend`].
+    pub fn extend<T: Component>(&self, entities: &[EntityId], components: Vec<T>) -> EcsResult<()> {
+        let typed = self.typed_or_default::<T>();
+        typed.extend(entities, components)?;
+
+        let hooks = typed.hooks();
+        if hooks.on_add.is_some() || hooks.on_insert.is_some() {
+            for entity in entities {
+                self.run_hook(hooks.on_add, *entity);
+                self.run_hook(hooks.on_insert, *entity);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the component of the given entity and returns it.
+    ///
+    /// This returns `Ok(None)` if the entity did not have this component and
+    /// [`EcsError::StorageLocked`] if the storage is currently borrowed.
+    pub fn take<T: Component>(&self, entity: EntityId) -> EcsResult<Option<T>> {
+        let Some(typed) = self.typed::<T>() else {
+            return Ok(None);
+        };
+
+        if typed.has_entity(entity) {
+            ensure_unlocked(typed)?;
+            let hooks = typed.hooks();
+            self.run_hook(hooks.on_replace, entity);
+            self.run_hook(hooks.on_remove, entity);
+        }
+
+        typed.take(entity)
+    }
+
+    /// Removes the component with the given type ID from an entity.
+    /// Returns `true` if the entity had this component.
+    pub fn remove_by_id(&self, type_id: TypeId, entity: EntityId) -> EcsResult<bool> {
+        let Some(store) = self.typeless(type_id) else {
+            return Ok(false);
+        };
+
+        if !store.has_entity(entity) {
+            return Ok(false);
+        }
+
+        ensure_unlocked(store)?;
+        let hooks = store.hooks();
+        self.run_hook(hooks.on_replace, entity);
+        self.run_hook(hooks.on_remove, entity);
+
+        store.remove(entity)
+    }
+
+    /// Removes all components with the given type IDs from an entity as a single operation.
+    ///
+    /// The `on_replace` and `on_remove` hooks of every component run before any of them is removed,
+    /// so hooks never observe a partially removed bundle.
+    pub fn remove_many(&self, entity: EntityId, type_ids: &[TypeId]) -> EcsResult<()> {
+        let stores: SmallVec<[&dyn TypelessStorage; 3]> = type_ids
+            .iter()
+            .filter_map(|type_id| self.typeless(*type_id))
+            .filter(|store| store.has_entity(entity))
+            .collect();
+
+        for store in &stores {
+            ensure_unlocked(*store)?;
+        }
+
+        for store in &stores {
+            let hooks = store.hooks();
+            self.run_hook(hooks.on_replace, entity);
+            self.run_hook(hooks.on_remove, entity);
+        }
+
+        for store in stores {
+            store.remove(entity)?;
         }
+
+        Ok(())
+    }
+
+    /// Registers lifecycle hooks for the component `T`.
+    ///
+    /// The closure receives the currently registered hooks, including the ones declared through the derive macro.
+    pub fn register_hooks<T: Component>(&self, f: impl FnOnce(&mut ComponentHooks)) {
+        let typed = self.typed_or_default::<T>();
+        f(&mut typed.hooks.write());
+    }
+
+    /// Calls the given hook if it exists and the world is still alive.
+    fn run_hook(&self, hook: Option<ComponentHook>, entity: EntityId) {
+        let Some(hook) = hook else {
+            return;
+        };
+
+        if let Some(world) = self.world.upgrade() {
+            hook(DeferredWorld::new(&world), entity);
+        }
+    }
+
+    /// Returns a reference to the typed storage of `T`, if it exists.
+    ///
+    /// Storages are boxed and never removed from the map, so the returned reference remains
+    /// valid for as long as the components themselves exist, even though it is not tied to the map guard.
+    /// Releasing the map guard is necessary to allow hooks to access other storages.
+    fn typed<T: Component>(&self) -> Option<&TypedStorage<T>> {
+        let store = self.map.get(&TypeId::of::<T>())?;
+        let typed: *const TypedStorage<T> = store
+            .value()
+            .as_any()
+            .downcast_ref()
+            .expect("Failed to downcast typeless storage. The wrong storage type has been inserted into component storage");
+
+        // Safety: See the function documentation.
+        Some(unsafe { &*typed })
+    }
+
+    /// Returns a reference to the typed storage of `T`, creating it if it does not exist yet.
+    fn typed_or_default<T: Component>(&self) -> &TypedStorage<T> {
+        let store = self.map.entry(TypeId::of::<T>()).or_insert_with(|| {
+            let typed = TypedStorage::<T>::default();
+            T::register_hooks(&mut typed.hooks.write());
+
+            Box::new(typed)
+        });
+
+        let typed: *const TypedStorage<T> = store
+            .value()
+            .as_any()
+            .downcast_ref()
+            .expect("Failed to downcast typeless storage. The wrong storage type has been inserted into component storage");
+
+        // Safety: See the documentation of `typed`.
+        unsafe { &*typed }
+    }
+
+    /// Returns a reference to the storage with the given type ID, if it exists.
+    ///
+    /// See [`typed`](Self::typed) for why the reference can outlive the map guard.
+    fn typeless(&self, type_id: TypeId) -> Option<&dyn TypelessStorage> {
+        let store = self.map.get(&type_id)?;
+        let typeless: *const dyn TypelessStorage = &**store.value();
+
+        // Safety: See the documentation of `typed`.
+        Some(unsafe { &*typeless })
+    }
+
+    /// Obtains a shared reference to the component of the given entity.
+    ///
+    /// This returns [`EcsError::StorageLocked`] if the storage is currently borrowed mutably
+    /// and [`EcsError::NotFound`] if the entity does not have this component.
+    pub fn get<T: Component>(&self, entity: EntityId) -> EcsResult<ComponentRef<'_, T>> {
+        let typed = self.typed::<T>().ok_or(EcsError::NotFound)?;
+        let guard = typed.lock.read()?;
+
+        let index = *typed.map.get(&entity).ok_or(EcsError::NotFound)?;
+        // Safety: The read lock acquired above prevents any mutable access to the storage.
+        let storage = unsafe { &*typed.storage.get() };
+
+        Ok(ComponentRef {
+            _guard: guard,
+            component: &storage[index],
+        })
+    }
+
+    /// Obtains a unique reference to the component of the given entity.
+    ///
+    /// This returns [`EcsError::StorageLocked`] if the storage is currently borrowed
+    /// and [`EcsError::NotFound`] if the entity does not have this component.
+    pub fn get_mut<T: Component>(&self, entity: EntityId) -> EcsResult<ComponentMut<'_, T>> {
+        let typed = self.typed::<T>().ok_or(EcsError::NotFound)?;
+        let guard = typed.lock.write()?;
+
+        let index = *typed.map.get(&entity).ok_or(EcsError::NotFound)?;
+        // Safety: The write lock acquired above ensures exclusive access to the storage.
+        let storage = unsafe { &mut *typed.storage.get() };
+
+        Ok(ComponentMut {
+            _guard: guard,
+            component: &mut storage[index],
+        })
     }
 
     pub fn has_component<T: Component>(&self, entity: EntityId) -> bool {
@@ -194,11 +769,97 @@ impl Components {
         }
     }
 
+    /// Write-locks all storages in preparation of [`clear_locked`](Self::clear_locked).
+    ///
+    /// This returns [`EcsError::StorageLocked`] and leaves all storages unlocked if any of them is currently borrowed.
+    pub(crate) fn lock_all(&self) -> EcsResult<()> {
+        lock_all(self.map.iter(), |store| store.value().lock())
+    }
+
+    /// Releases the locks acquired by [`lock_all`](Self::lock_all) without changing anything.
+    ///
+    /// # Safety
+    ///
+    /// All storages must have been locked by [`lock_all`](Self::lock_all).
+    pub(crate) unsafe fn unlock_all(&self) {
+        for store in self.map.iter() {
+            unsafe { store.value().lock().force_release_write() };
+        }
+    }
+
+    /// Drops all components of all entities without running any hooks and releases the locks again.
+    ///
+    /// The storages themselves are kept, since references handed out by [`typed`](Self::typed) are not tied
+    /// to the map. This also keeps all registered hooks.
+    ///
+    /// # Safety
+    ///
+    /// All storages must have been locked by [`lock_all`](Self::lock_all).
+    pub(crate) unsafe fn clear_locked(&self) {
+        for store in self.map.iter() {
+            let store = store.value();
+            unsafe {
+                store.clear();
+                store.lock().force_release_write();
+            }
+        }
+    }
+
     pub fn despawn(&self, entity: EntityId) {
-        self.map.retain(|_, store| {
-            !store
-                .remove(entity)
-                .expect("Cannot despawn components, storage is locked.")
-        });
+        self.try_despawn(entity)
+            .expect("Cannot despawn components, storage is locked.");
+    }
+
+    /// Removes all components of the given entity.
+    ///
+    /// Storages are left in place even when they become empty, so that references obtained through
+    /// [`get`](Self::get) never outlive their storage. If a storage is locked, the remaining storages are
+    /// still cleared and the first error is returned.
+    pub fn try_despawn(&self, entity: EntityId) -> EcsResult<()> {
+        // Collect the types first, hooks cannot run while the map is being iterated.
+        let type_ids: Vec<TypeId> = self
+            .map
+            .iter()
+            .filter(|store| store.value().has_entity(entity))
+            .map(|store| *store.key())
+            .collect();
+
+        let mut result = Ok(());
+        for type_id in type_ids {
+            if let Err(err) = self.remove_by_id(type_id, entity) {
+                result = result.and(Err(err));
+            }
+        }
+
+        result
     }
 }
+
+/// Write-locks all items, or none of them if any of them is already in use.
+///
+/// The locks are not tied to a guard and stay held until the caller releases them: [`Components::unlock_all`]
+/// releases them without touching the data, [`Components::clear_locked`] drops the components and releases
+/// them afterwards, and `Resources::clear_locked` drops the resources together with their locks.
+pub(crate) fn lock_all<T, I, F>(items: I, lock: F) -> EcsResult<()>
+where
+    I: Iterator<Item = T>,
+    F: Fn(&T) -> &PersistentLock,
+{
+    let mut acquired = Vec::new();
+    for item in items {
+        let result = lock(&item).write().map(std::mem::forget);
+        match result {
+            Ok(()) => acquired.push(item),
+            Err(err) => {
+                for item in &acquired {
+                    // Safety: This lock has been acquired by us above.
+                    unsafe { lock(item).force_release_write() };
+                }
+
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use crate::{
+    Event, EventWatcher, FnContainer, ParameterizedSystem, Res, Resource, State, System,
+    SystemParams, World,
+};
+
+/// A read-only system that decides whether other systems should run.
+///
+/// Conditions are regular functions returning `bool` and can use the same parameters as systems,
+/// as long as they do not require mutable access. They are evaluated before the parameters of the system
+/// they guard are fetched, so skipped systems do not lock anything.
+///
+/// ```ignore
+/// fn not_paused(paused: Res<Paused>) -> bool {
+///     !paused.0
+/// }
+///
+/// schedule.add_system(movement).run_if(not_paused);
+/// schedule.add_system(autosave).run_if(every_n_ticks(1200));
+/// ```
+pub trait Condition: System {
+    /// Evaluates the condition.
+    fn evaluate(&self, world: &Arc<World>) -> bool;
+}
+
+impl<P, F> Condition for FnContainer<P, bool, F>
+where
+    P: SystemParams,
+    F: ParameterizedSystem<P, bool>,
+    FnContainer<P, bool, F>: System,
+{
+    fn evaluate(&self, world: &Arc<World>) -> bool {
+        self.system.call(world, &self.state)
+    }
+}
+
+/// Runs the system only if the resource `R` exists.
+pub fn resource_exists<R: Resource>() -> impl Fn(Option<Res<R>>) -> bool + Send + Sync + 'static {
+    |resource: Option<Res<R>>| resource.is_some()
+}
+
+/// Runs the system only if the resource `S` exists and is equal to `state`.
+///
+/// This is useful for game states stored as a resource, such as whether the game is paused.
+pub fn in_state<S>(state: S) -> impl Fn(Option<Res<S>>) -> bool + Send + Sync + 'static
+where
+    S: Resource + PartialEq,
+{
+    move |current: Option<Res<S>>| current.is_some_and(|current| *current == state)
+}
+
+/// Runs the system only if events of type `E` have been sent since the condition was last evaluated.
+///
+/// The condition only peeks at the event bus through an [`EventWatcher`] and does not register as a reader,
+/// so it neither consumes events nor keeps them alive.
+pub fn on_event<E: Event>() -> impl Fn(EventWatcher<E>) -> bool + Send + Sync + 'static {
+    |mut watcher: EventWatcher<E>| watcher.has_new()
+}
+
+/// Runs the system on the first and then every `n`-th evaluation of the condition.
+///
+/// The condition is evaluated once every time the schedule runs, unless a condition of one of the
+/// system's sets already prevented it from running.
+///
+/// # Panics
+///
+/// Panics if `n` is zero.
+pub fn every_n_ticks(n: usize) -> impl Fn(State<usize>) -> bool + Send + Sync + 'static {
+    assert_ne!(n, 0, "every_n_ticks requires a non-zero interval");
+
+    move |mut evaluations: State<usize>| {
+        let run = evaluations.is_multiple_of(n);
+        *evaluations += 1;
+        run
+    }
+}
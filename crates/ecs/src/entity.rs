@@ -1,5 +1,9 @@
-use crate::{filter::FilterParams, Component, QueryParams, World};
+use crate::{
+    filter::FilterParams, Component, ComponentMut, ComponentRef, EcsResult, QueryParams,
+    SpawnBundle, World,
+};
 use bitvec::vec::BitVec;
+use ecs_derive::Component;
 use parking_lot::{RwLock, RwLockReadGuard};
 use std::any::TypeId;
 use std::fmt::Debug;
@@ -7,6 +11,15 @@ use std::iter::FusedIterator;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
+/// Marks an entity as disabled.
+///
+/// Disabled entities keep all of their components but are skipped by every query,
+/// unless the query opts in with the [`WithDisabled`](crate::WithDisabled) filter or only
+/// visits disabled entities with `With<Disabled>`.
+/// Use [`Entity::disable`] and [`Entity::enable`] to toggle this.
+#[derive(Debug, Default, Copy, Clone, Component)]
+pub struct Disabled;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct EntityId(pub(crate) usize);
 
@@ -31,13 +44,51 @@ impl Entity {
         self.world.components.has_component::<T>(self.id)
     }
 
-    // pub fn get<T: Component>(&self) -> Option<&T> {
-    //     self.world.components.get(self.id)
-    // }
+    /// Obtains a shared reference to a component of this entity.
+    ///
+    /// The component storage remains locked for as long as the returned reference exists.
+    /// If a running system currently has the storage borrowed mutably, this returns [`EcsError::StorageLocked`].
+    ///
+    /// [`EcsError::StorageLocked`]: crate::EcsError::StorageLocked
+    pub fn get<T: Component>(&self) -> EcsResult<ComponentRef<'_, T>> {
+        self.world.components.get(self.id)
+    }
+
+    /// Obtains a unique reference to a component of this entity.
+    ///
+    /// The component storage remains locked for as long as the returned reference exists.
+    /// If the storage is currently borrowed in any way, this returns [`EcsError::StorageLocked`].
+    ///
+    /// [`EcsError::StorageLocked`]: crate::EcsError::StorageLocked
+    pub fn get_mut<T: Component>(&self) -> EcsResult<ComponentMut<'_, T>> {
+        self.world.components.get_mut(self.id)
+    }
+
+    /// Disables the entity, excluding it from all queries that do not use [`WithDisabled`](crate::WithDisabled).
+    /// The actual change is only performed after all systems have completed running.
+    pub fn disable(&self) {
+        self.insert(Disabled);
+    }
+
+    /// Enables a previously disabled entity.
+    /// The actual change is only performed after all systems have completed running.
+    pub fn enable(&self) {
+        self.remove::<Disabled>();
+    }
+
+    /// Returns whether the entity is currently disabled.
+    pub fn is_disabled(&self) -> bool {
+        self.has::<Disabled>()
+    }
 
-    // pub fn get_mut<T: Component>(&self) -> Option<&mut T> {
-    //     self.world.components.get_mut(self.id)
-    // }
+    /// Inserts a bundle of components into the entity, replacing any components of the same type.
+    /// The actual change is only performed after all systems have completed running in order to prevent issues.
+    ///
+    /// Components required by the inserted components are added as well if the entity does not have them yet.
+    /// If the entity has been despawned by the time the insertion is performed, this does nothing.
+    pub fn insert<B: SpawnBundle + Send + 'static>(&self, bundle: B) {
+        self.world.scheduler.schedule_insert(self.id, bundle);
+    }
 
     /// Removes a component from an entity. The actual change is only performed
     /// after all systems have completed running in order to prevent issues.
@@ -49,6 +100,126 @@ impl Entity {
             .scheduler
             .schedule_remove_component(self.id, type_id);
     }
+
+    /// Removes all components in the bundle `B` from the entity. Like [`remove`](Self::remove),
+    /// the components are only removed after all systems have completed running.
+    ///
+    /// Components in the bundle that the entity does not have are ignored.
+    pub fn remove_bundle<B: SpawnBundle>(&self) {
+        self.world
+            .scheduler
+            .schedule_remove_components(self.id, B::type_ids());
+    }
+}
+
+/// A borrowed handle to an entity.
+///
+/// Unlike [`Entity`], this does not hold on to the world, making it free to create.
+/// This is the preferred way to refer to entities in query results, use [`to_entity`](Self::to_entity)
+/// to obtain an owned handle when the entity has to be stored.
+#[derive(Copy, Clone)]
+pub struct EntityRef<'w> {
+    pub(crate) world: &'w Arc<World>,
+    pub(crate) id: EntityId,
+}
+
+impl<'w> EntityRef<'w> {
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+
+    pub fn has<T: Component>(&self) -> bool {
+        self.world.components.has_component::<T>(self.id)
+    }
+
+    /// Returns whether the entity is currently disabled.
+    pub fn is_disabled(&self) -> bool {
+        self.has::<Disabled>()
+    }
+
+    /// Obtains a shared reference to a component of this entity, see [`Entity::get`].
+    pub fn get<T: Component>(&self) -> EcsResult<ComponentRef<'w, T>> {
+        self.world.components.get(self.id)
+    }
+
+    /// Creates an owned handle to this entity.
+    pub fn to_entity(&self) -> Entity {
+        Entity {
+            world: Arc::clone(self.world),
+            id: self.id,
+        }
+    }
+
+    /// Returns whether the entity should be visited by a query with the given parameters and filters.
+    pub(crate) fn matches<Q: QueryParams, F: FilterParams>(&self) -> bool {
+        let include_disabled = Q::include_disabled() || F::include_disabled();
+        (include_disabled || !self.is_disabled()) && Q::filter(self) && F::filter(self)
+    }
+}
+
+impl Debug for EntityRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EntityRef").field(&self.id).finish()
+    }
+}
+
+/// A borrowed handle to an entity that can also modify it.
+///
+/// Like [`EntityRef`], this does not hold on to the world. Components can be mutated through
+/// the lock-guarded [`get_mut`](Self::get_mut), structural changes are deferred like those made through [`Entity`].
+pub struct EntityMut<'w> {
+    pub(crate) world: &'w Arc<World>,
+    pub(crate) id: EntityId,
+}
+
+impl<'w> EntityMut<'w> {
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+
+    pub fn has<T: Component>(&self) -> bool {
+        self.world.components.has_component::<T>(self.id)
+    }
+
+    /// Obtains a shared reference to a component of this entity, see [`Entity::get`].
+    pub fn get<T: Component>(&self) -> EcsResult<ComponentRef<'w, T>> {
+        self.world.components.get(self.id)
+    }
+
+    /// Obtains a unique reference to a component of this entity, see [`Entity::get_mut`].
+    pub fn get_mut<T: Component>(&mut self) -> EcsResult<ComponentMut<'w, T>> {
+        self.world.components.get_mut(self.id)
+    }
+
+    /// Inserts a bundle of components into the entity, see [`Entity::insert`].
+    pub fn insert<B: SpawnBundle + Send + 'static>(&mut self, bundle: B) {
+        self.world.scheduler.schedule_insert(self.id, bundle);
+    }
+
+    /// Removes a component from the entity, see [`Entity::remove`].
+    pub fn remove<T: Component>(&mut self) {
+        self.world
+            .scheduler
+            .schedule_remove_component(self.id, TypeId::of::<T>());
+    }
+
+    /// Despawns the entity, see [`Entity::despawn`].
+    pub fn despawn(self) {
+        self.world.scheduler.schedule_despawn(self.id);
+    }
+
+    /// Reborrows this handle as an [`EntityRef`].
+    pub fn as_ref(&self) -> EntityRef<'w> {
+        EntityRef {
+            world: self.world,
+            id: self.id,
+        }
+    }
+
+    /// Creates an owned handle to this entity.
+    pub fn to_entity(&self) -> Entity {
+        self.as_ref().to_entity()
+    }
 }
 
 #[derive(Default)]
@@ -85,6 +256,38 @@ impl Entities {
         EntityId(id)
     }
 
+    /// Allocates `count` entity IDs at once, filling gaps left by despawned entities first.
+    pub fn alloc_many(&self, count: usize) -> Vec<EntityId> {
+        let mut lock = self.indices.write();
+        let mut ids = Vec::with_capacity(count);
+
+        ids.extend(lock.iter_zeros().take(count).map(EntityId));
+        for id in &ids {
+            lock.set(id.0, true);
+        }
+
+        let len = lock.len();
+        let remaining = count - ids.len();
+        lock.resize(len + remaining, true);
+        ids.extend((len..len + remaining).map(EntityId));
+
+        ids
+    }
+
+    /// Returns whether the given entity is currently alive.
+    pub fn contains(&self, entity: EntityId) -> bool {
+        self.indices
+            .read()
+            .get(entity.0)
+            .map(|bit| *bit)
+            .unwrap_or(false)
+    }
+
+    /// Frees all entity IDs.
+    pub fn clear(&self) {
+        self.indices.write().clear();
+    }
+
     pub fn free(&self, entity: EntityId) {
         self.indices.write().set(entity.0, false);
     }
@@ -96,14 +299,14 @@ impl Entities {
         }
     }
 
-    pub fn iter<'a, Q, F>(&'a self, world: &Arc<World>) -> EntityIter<'a, Q, F>
+    pub fn iter<'a, Q, F>(&'a self, world: &'a Arc<World>) -> EntityIter<'a, Q, F>
     where
         Q: QueryParams,
         F: FilterParams,
     {
         let entities = self.indices.read();
         EntityIter {
-            world: Arc::clone(world),
+            world,
             entities,
             iter_index: 0,
             _marker: PhantomData,
@@ -116,8 +319,9 @@ where
     Q: QueryParams,
     F: FilterParams,
 {
-    pub world: Arc<World>,
+    pub world: &'w Arc<World>,
     pub entities: RwLockReadGuard<'w, BitVec>,
+    /// The index to continue searching for alive entities from.
     pub iter_index: usize,
     pub _marker: PhantomData<&'w (Q, F)>,
 }
@@ -127,20 +331,20 @@ where
     Q: QueryParams,
     F: FilterParams,
 {
-    type Item = Entity;
+    type Item = EntityRef<'w>;
 
     fn next(&mut self) -> Option<Self::Item> {
         // Use a loop rather than recursion for cache reasons.
         loop {
-            let next_id = self.entities.iter_ones().nth(self.iter_index)?;
+            let next_id = self.iter_index + self.entities.get(self.iter_index..)?.first_one()?;
 
-            self.iter_index += 1;
-            let entity = Entity {
-                world: self.world.clone(),
+            self.iter_index = next_id + 1;
+            let entity = EntityRef {
+                world: self.world,
                 id: EntityId(next_id),
             };
 
-            if Q::filter(&entity) && F::filter(&entity) {
+            if entity.matches::<Q, F>() {
                 break Some(entity);
             }
         }
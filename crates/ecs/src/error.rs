@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-#[derive(Debug, Error, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum EcsError {
     #[error("the requested resource was not found")]
     NotFound,
@@ -9,6 +9,30 @@ pub enum EcsError {
         "the operation was rejected because the requested component storage is already locked: {0}"
     )]
     StorageLocked(&'static str),
+    /// The ordering constraints of a schedule require the listed systems to run before themselves.
+    #[error("the ordering constraints of systems {0:?} form a cycle")]
+    ScheduleCycle(Vec<&'static str>),
+    /// A system borrows the listed types in ways that conflict with each other, for example both mutably and immutably.
+    #[error("system {system} requests conflicting access to {types:?}")]
+    ConflictingAccess {
+        system: &'static str,
+        types: Vec<&'static str>,
+    },
+    /// A bundle contains the same component type more than once.
+    #[error("bundle {0} contains the same component type more than once")]
+    DuplicateComponent(&'static str),
+    /// The entity cannot become a child of itself or one of its descendants.
+    #[error("the hierarchy change would make an entity its own ancestor")]
+    HierarchyCycle,
+    /// A system takes an [`In`](crate::In) parameter but is not the receiving side of a pipe.
+    #[error("system {0} takes piped input but does not receive the output of another system")]
+    UnpipedInput(&'static str),
 }
 
 pub type EcsResult<T> = Result<T, EcsError>;
+
+/// An error returned by a fallible system.
+pub type SystemError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The result of running a system, see [`Fallible`](crate::Fallible).
+pub type SystemResult = Result<(), SystemError>;
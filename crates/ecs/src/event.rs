@@ -118,6 +118,11 @@ impl Events {
         Some(event)
     }
 
+    /// Drops all unread events and event buses.
+    pub(crate) fn clear(&self) {
+        self.storage.clear();
+    }
+
     /// Registers a reader to the specified event bus.
     ///
     /// This should be done for all systems before running any of them.
@@ -156,6 +161,18 @@ impl Events {
         table.readers.fetch_sub(1, Ordering::SeqCst);
     }
 
+    /// Returns the number of readers registered to the specified event bus.
+    pub fn reader_count<E: Event>(&self) -> usize {
+        self.storage.get(&TypeId::of::<E>()).map_or(0, |table| {
+            let table: &EventBus<E> = table
+                .as_any()
+                .downcast_ref()
+                .expect("EventTable type ID does not match event type ID");
+
+            table.readers.load(Ordering::SeqCst)
+        })
+    }
+
     pub fn next_id<E: Event>(&self) -> Option<EventId> {
         let table = self.storage.get_mut(&TypeId::of::<E>())?;
         let table: &EventBus<E> = table.as_any().downcast_ref()?;
@@ -262,6 +279,44 @@ impl<E: Event> SystemParam for EventReader<E> {
     }
 }
 
+/// Observes whether events of type `E` have been written, without reading them.
+///
+/// Unlike an [`EventReader`], this is not registered as a reader of the event bus,
+/// so events are never kept around for it. It is used by the [`on_event`](crate::on_event) condition.
+pub struct EventWatcher<E: Event> {
+    world: Arc<World>,
+    state: Arc<EventState<E>>,
+}
+
+impl<E: Event> EventWatcher<E> {
+    /// Returns whether any events have been written since the last call, or since the watcher was created.
+    pub fn has_new(&mut self) -> bool {
+        let next_id = self.world.events.next_id::<E>().map(|x| x.0).unwrap_or(0);
+        let last_seen = self.state.last_read.swap(next_id, Ordering::SeqCst);
+
+        next_id > last_seen
+    }
+}
+
+impl<E: Event> SystemParam for EventWatcher<E> {
+    type State = EventState<E>;
+
+    fn descriptor() -> SystemParamDescriptor {
+        SystemParamDescriptor::EventReader
+    }
+
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, state: &Arc<Self::State>) -> Self {
+        EventWatcher {
+            world: Arc::clone(world),
+            state: Arc::clone(state),
+        }
+    }
+
+    fn state(world: &Arc<World>) -> Arc<Self::State> {
+        EventReader::<E>::state(world)
+    }
+}
+
 pub struct EventIterator<'reader, E: Event> {
     reader: &'reader mut EventReader<E>,
 }
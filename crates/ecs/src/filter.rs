@@ -1,8 +1,14 @@
-use crate::{Component, Entity};
+use crate::{Component, Disabled, EntityRef};
+use std::any::TypeId;
 use std::marker::PhantomData;
 
 pub trait Filter {
-    fn filter(entity: &Entity) -> bool;
+    /// Whether queries using this filter should also visit [`Disabled`] entities.
+    fn include_disabled() -> bool {
+        false
+    }
+
+    fn filter(entity: &EntityRef) -> bool;
 }
 
 pub struct With<T: Component> {
@@ -10,7 +16,12 @@ pub struct With<T: Component> {
 }
 
 impl<T: Component> Filter for With<T> {
-    fn filter(entity: &Entity) -> bool {
+    /// `With<Disabled>` only matches disabled entities, so it has to include them.
+    fn include_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
+
+    fn filter(entity: &EntityRef) -> bool {
         entity.has::<T>()
     }
 }
@@ -20,17 +31,30 @@ pub struct Without<T: Component> {
 }
 
 impl<T: Component> Filter for Without<T> {
-    fn filter(entity: &Entity) -> bool {
+    fn filter(entity: &EntityRef) -> bool {
         !entity.has::<T>()
     }
 }
 
+/// Makes a query include [`Disabled`] entities, which are skipped by default.
+pub struct WithDisabled;
+
+impl Filter for WithDisabled {
+    fn include_disabled() -> bool {
+        true
+    }
+
+    fn filter(_entity: &EntityRef) -> bool {
+        true
+    }
+}
+
 pub struct Added<T: Component> {
     _marker: PhantomData<T>,
 }
 
 impl<T: Component> Filter for Added<T> {
-    fn filter(_entity: &Entity) -> bool {
+    fn filter(_entity: &EntityRef) -> bool {
         todo!()
     }
 }
@@ -40,7 +64,7 @@ pub struct Removed<T: Component> {
 }
 
 impl<T: Component> Filter for Removed<T> {
-    fn filter(_entity: &Entity) -> bool {
+    fn filter(_entity: &EntityRef) -> bool {
         todo!()
     }
 }
@@ -50,24 +74,33 @@ pub struct Changed<T: Component> {
 }
 
 impl<T: Component> Filter for Changed<T> {
-    fn filter(_entity: &Entity) -> bool {
+    fn filter(_entity: &EntityRef) -> bool {
         todo!()
     }
 }
 
 pub trait FilterParams {
-    fn filter(entity: &Entity) -> bool;
+    /// Whether queries using these filters should also visit [`Disabled`] entities.
+    fn include_disabled() -> bool {
+        false
+    }
+
+    fn filter(entity: &EntityRef) -> bool;
 }
 
 impl FilterParams for () {
-    fn filter(_entity: &Entity) -> bool {
+    fn filter(_entity: &EntityRef) -> bool {
         true
     }
 }
 
 impl<F: Filter> FilterParams for F {
-    fn filter(entity: &Entity) -> bool {
-        F::filter(&entity)
+    fn include_disabled() -> bool {
+        F::include_disabled()
+    }
+
+    fn filter(entity: &EntityRef) -> bool {
+        F::filter(entity)
     }
 }
 
@@ -76,7 +109,11 @@ where
     F0: FilterParams,
     F1: FilterParams,
 {
-    fn filter(entity: &Entity) -> bool {
+    fn include_disabled() -> bool {
+        F0::include_disabled() || F1::include_disabled()
+    }
+
+    fn filter(entity: &EntityRef) -> bool {
         F0::filter(entity) && F1::filter(entity)
     }
 }
@@ -0,0 +1,243 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use ecs_derive::Component;
+use smallvec::{smallvec, SmallVec};
+
+use crate::{DeferredWorld, EcsError, EcsResult, Entity, EntityId, World};
+
+/// The parent of an entity in the entity hierarchy.
+///
+/// This component cannot be inserted directly, use [`Entity::set_parent`] or [`Entity::add_child`] instead.
+/// The ECS keeps it consistent with the [`Children`] of the parent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Component)]
+#[component(on_insert = parent_inserted, on_replace = parent_replaced)]
+pub struct Parent(pub(crate) EntityId);
+
+impl Parent {
+    /// The ID of the parent entity.
+    pub fn get(&self) -> EntityId {
+        self.0
+    }
+}
+
+/// The children of an entity in the entity hierarchy.
+///
+/// This component is managed by the ECS and is updated whenever the [`Parent`] of another entity changes.
+/// Removing it from an entity orphans all of its children.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+#[component(on_remove = children_removed)]
+pub struct Children(pub(crate) SmallVec<[EntityId; 8]>);
+
+impl Deref for Children {
+    type Target = [EntityId];
+
+    fn deref(&self) -> &[EntityId] {
+        &self.0
+    }
+}
+
+/// Registers the entity with its new parent.
+fn parent_inserted(world: DeferredWorld, entity: EntityId) {
+    let Ok(parent) = world.get::<Parent>(entity).map(|p| p.0) else {
+        return;
+    };
+
+    world.schedule_command(move |world| add_to_children(world, parent, entity));
+}
+
+/// Unregisters the entity from its old parent, either because it is getting a new parent or because it has been removed.
+fn parent_replaced(world: DeferredWorld, entity: EntityId) {
+    let Ok(parent) = world.get::<Parent>(entity).map(|p| p.0) else {
+        return;
+    };
+
+    world.schedule_command(move |world| remove_from_children(world, parent, entity));
+}
+
+/// Orphans all children of the entity.
+fn children_removed(world: DeferredWorld, entity: EntityId) {
+    let Ok(children) = world.get::<Children>(entity).map(|c| c.0.clone()) else {
+        return;
+    };
+
+    world.schedule_command(move |world| {
+        for child in children {
+            let is_child = world
+                .components
+                .get::<Parent>(child)
+                .is_ok_and(|parent| parent.0 == entity);
+
+            if is_child {
+                world
+                    .components
+                    .take::<Parent>(child)
+                    .expect("Cannot update hierarchy, storage is locked.");
+            }
+        }
+    });
+}
+
+fn add_to_children(world: &Arc<World>, parent: EntityId, child: EntityId) {
+    if !world.entities.contains(parent) {
+        return;
+    }
+
+    match world.components.get_mut::<Children>(parent) {
+        Ok(mut children) => {
+            if !children.0.contains(&child) {
+                children.0.push(child);
+            }
+        }
+        Err(EcsError::NotFound) => {
+            world
+                .components
+                .insert(parent, Children(smallvec![child]))
+                .expect("Cannot update hierarchy, storage is locked.");
+        }
+        Err(err) => panic!("Cannot update hierarchy: {err}"),
+    }
+}
+
+fn remove_from_children(world: &Arc<World>, parent: EntityId, child: EntityId) {
+    let is_empty = match world.components.get_mut::<Children>(parent) {
+        Ok(mut children) => {
+            children.0.retain(|c| *c != child);
+            children.0.is_empty()
+        }
+        Err(EcsError::NotFound) => return,
+        Err(err) => panic!("Cannot update hierarchy: {err}"),
+    };
+
+    if is_empty {
+        world
+            .components
+            .take::<Children>(parent)
+            .expect("Cannot update hierarchy, storage is locked.");
+    }
+}
+
+/// Collects all descendants of the entity in depth-first order.
+fn collect_descendants(world: &World, entity: EntityId, out: &mut Vec<EntityId>) -> EcsResult<()> {
+    let children = match world.components.get::<Children>(entity) {
+        Ok(children) => children.0.clone(),
+        Err(EcsError::NotFound) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    for child in children {
+        out.push(child);
+        collect_descendants(world, child, out)?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether `ancestor` is `entity` itself or one of its ancestors.
+fn is_ancestor_or_self(world: &World, ancestor: EntityId, entity: EntityId) -> EcsResult<bool> {
+    let mut current = entity;
+    loop {
+        if current == ancestor {
+            return Ok(true);
+        }
+
+        current = match world.components.get::<Parent>(current) {
+            Ok(parent) => parent.0,
+            Err(EcsError::NotFound) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+    }
+}
+
+impl Entity {
+    /// Makes this entity a child of `parent`, removing it from its previous parent.
+    /// The actual change is only performed after all systems have completed running.
+    ///
+    /// Returns [`EcsError::HierarchyCycle`] if `parent` is this entity itself or one of its descendants,
+    /// and an error if the hierarchy cannot be checked because the [`Parent`] storage is locked.
+    /// If another change queued in the same tick creates the cycle instead, this change is discarded when it is applied.
+    pub fn set_parent(&self, parent: EntityId) -> EcsResult<()> {
+        if is_ancestor_or_self(&self.world, self.id, parent)? {
+            return Err(EcsError::HierarchyCycle);
+        }
+
+        let child = self.id;
+        self.world.scheduler.schedule_command(move |world| {
+            let is_cycle = is_ancestor_or_self(world, child, parent)
+                .expect("Cannot update hierarchy, storage is locked.");
+
+            if world.entities.contains(child) && !is_cycle {
+                world
+                    .components
+                    .insert(child, Parent(parent))
+                    .expect("Cannot update hierarchy, storage is locked.");
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Detaches this entity from its parent, if it has one.
+    /// The actual change is only performed after all systems have completed running.
+    pub fn remove_parent(&self) {
+        self.remove::<Parent>();
+    }
+
+    /// Makes `child` a child of this entity, see [`set_parent`](Self::set_parent).
+    pub fn add_child(&self, child: EntityId) -> EcsResult<()> {
+        self.world.entity(child).set_parent(self.id)
+    }
+
+    /// Returns the parent of this entity, if it has one.
+    pub fn parent(&self) -> EcsResult<Option<EntityId>> {
+        match self.get::<Parent>() {
+            Ok(parent) => Ok(Some(parent.0)),
+            Err(EcsError::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the direct children of this entity.
+    pub fn children(&self) -> EcsResult<SmallVec<[EntityId; 8]>> {
+        match self.get::<Children>() {
+            Ok(children) => Ok(children.0.clone()),
+            Err(EcsError::NotFound) => Ok(SmallVec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns all descendants of this entity in depth-first order.
+    pub fn descendants(&self) -> EcsResult<Vec<EntityId>> {
+        let mut descendants = Vec::new();
+        collect_descendants(&self.world, self.id, &mut descendants)?;
+
+        Ok(descendants)
+    }
+
+    /// Returns all ancestors of this entity, starting with its parent and ending with the root.
+    pub fn ancestors(&self) -> EcsResult<Vec<EntityId>> {
+        let mut ancestors = Vec::new();
+        let mut current = self.parent()?;
+        while let Some(parent) = current {
+            ancestors.push(parent);
+            current = self.world.entity(parent).parent()?;
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Despawns the entity together with all of its descendants.
+    /// Like [`despawn`](Self::despawn), the entire subtree is removed after all systems have completed.
+    pub fn despawn_recursive(self) {
+        let entity = self.id;
+        self.world.scheduler.schedule_command(move |world| {
+            let mut subtree = vec![entity];
+            collect_descendants(world, entity, &mut subtree)
+                .expect("Cannot despawn hierarchy, storage is locked.");
+
+            for entity in subtree {
+                world.scheduler.schedule_despawn(entity);
+            }
+        });
+    }
+}
@@ -1,12 +1,21 @@
 #[cfg(test)]
 mod test;
 
+// Allows the derive macros to refer to `::ecs` from within this crate as well.
+extern crate self as ecs;
+
+mod background;
 mod component;
+mod condition;
 mod entity;
 mod error;
 mod event;
 mod filter;
+mod hierarchy;
+mod param_set;
+mod pipe;
 mod query;
+mod relation;
 mod resource;
 mod scheduler;
 mod state;
@@ -14,13 +23,20 @@ mod system;
 mod util;
 mod world;
 
+pub use background::*;
 pub use component::*;
+pub use condition::*;
 pub use entity::*;
 pub use error::*;
 pub use event::*;
 pub use filter::*;
+pub use hierarchy::*;
+pub use param_set::*;
+pub use pipe::*;
 pub use query::*;
+pub use relation::*;
 pub use resource::*;
+pub use scheduler::*;
 pub use state::*;
 pub use system::*;
 pub use util::*;
@@ -29,14 +45,20 @@ pub use world::*;
 pub mod prelude {
     #![allow(unused)]
 
+    use super::background::Background;
     use super::component::Component;
-    use super::entity::{Entity, EntityId};
-    use super::event::{Event, EventId, EventReader, EventWriter};
-    use super::filter::{Added, Changed, Removed, With, Without};
+    use super::entity::{Disabled, Entity, EntityId, EntityMut, EntityRef};
+    use super::event::{Event, EventId, EventReader, EventWatcher, EventWriter};
+    use super::filter::{Added, Changed, Removed, With, WithDisabled, Without};
+    use super::hierarchy::{Children, Parent};
+    use super::param_set::ParamSet;
+    use super::pipe::{In, PipeSystem};
     use super::query::Query;
+    use super::relation::{Related, Relation};
     use super::resource::{Res, ResMut, Resource};
+    use super::scheduler::{IntoSystems, ScheduleLabel};
     use super::state::State;
-    use super::world::World;
+    use super::world::{ExclusiveWorld, World};
 }
 
 pub(crate) mod sealed {
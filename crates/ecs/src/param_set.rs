@@ -0,0 +1,179 @@
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use crate::{
+    scheduler::SystemParamDescriptor,
+    sealed::{self, Sealer},
+    SystemParam, World,
+};
+
+/// A set of system parameters that may conflict with each other, of which only one can be used at a time.
+///
+/// The scheduler accounts for the combined access of all parameters in the set, but a system using a `ParamSet`
+/// is not rejected for the conflicts between them. Instead, the parameters are only fetched when borrowed through
+/// [`p0`](ParamSet::p0), [`p1`](ParamSet::p1), etc. and release their locks again when the borrow ends.
+///
+/// ```ignore
+/// fn follow(mut set: ParamSet<(Query<&mut Transform, With<Player>>, Query<&Transform, With<Mob>>)>) {
+///     let target = set.p1().into_iter().next().copied();
+///     for transform in &*set.p0() {
+///         transform.look_at(target);
+///     }
+/// }
+/// ```
+pub struct ParamSet<T: ParamSetParams> {
+    world: Arc<World>,
+    state: Arc<T::States>,
+}
+
+/// The parameters that can be grouped in a [`ParamSet`].
+///
+/// This is implemented for tuples of system parameters.
+pub trait ParamSetParams: Send + Sync {
+    type States: Send + Sync;
+
+    fn descriptors() -> Vec<SystemParamDescriptor>;
+    fn states(world: &Arc<World>) -> Self::States;
+    fn init(world: &Arc<World>, states: &Self::States);
+    fn destroy(world: &Arc<World>, states: &Self::States);
+}
+
+/// A parameter borrowed from a [`ParamSet`]. Its locks are released when this is dropped.
+pub struct ParamSetItem<'s, P> {
+    param: P,
+    _marker: PhantomData<&'s mut ()>,
+}
+
+impl<P> Deref for ParamSetItem<'_, P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        &self.param
+    }
+}
+
+impl<P> DerefMut for ParamSetItem<'_, P> {
+    fn deref_mut(&mut self) -> &mut P {
+        &mut self.param
+    }
+}
+
+impl<T: ParamSetParams> SystemParam for ParamSet<T> {
+    type State = T::States;
+
+    fn descriptor() -> SystemParamDescriptor {
+        SystemParamDescriptor::ParamSet(T::descriptors())
+    }
+
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, state: &Arc<Self::State>) -> Self {
+        ParamSet {
+            world: Arc::clone(world),
+            state: Arc::clone(state),
+        }
+    }
+
+    fn state(world: &Arc<World>) -> Arc<Self::State> {
+        Arc::new(T::states(world))
+    }
+
+    fn init(world: &Arc<World>, state: &Arc<Self::State>) {
+        T::init(world, state);
+    }
+
+    fn destroy(world: &Arc<World>, state: &Arc<Self::State>) {
+        T::destroy(world, state);
+    }
+}
+
+impl<P0: SystemParam, P1: SystemParam> ParamSetParams for (P0, P1) {
+    type States = (Arc<P0::State>, Arc<P1::State>);
+
+    fn descriptors() -> Vec<SystemParamDescriptor> {
+        vec![P0::descriptor(), P1::descriptor()]
+    }
+
+    fn states(world: &Arc<World>) -> Self::States {
+        (P0::state(world), P1::state(world))
+    }
+
+    fn init(world: &Arc<World>, states: &Self::States) {
+        P0::init(world, &states.0);
+        P1::init(world, &states.1);
+    }
+
+    fn destroy(world: &Arc<World>, states: &Self::States) {
+        P0::destroy(world, &states.0);
+        P1::destroy(world, &states.1);
+    }
+}
+
+impl<P0: SystemParam, P1: SystemParam, P2: SystemParam> ParamSetParams for (P0, P1, P2) {
+    type States = (Arc<P0::State>, Arc<P1::State>, Arc<P2::State>);
+
+    fn descriptors() -> Vec<SystemParamDescriptor> {
+        vec![P0::descriptor(), P1::descriptor(), P2::descriptor()]
+    }
+
+    fn states(world: &Arc<World>) -> Self::States {
+        (P0::state(world), P1::state(world), P2::state(world))
+    }
+
+    fn init(world: &Arc<World>, states: &Self::States) {
+        P0::init(world, &states.0);
+        P1::init(world, &states.1);
+        P2::init(world, &states.2);
+    }
+
+    fn destroy(world: &Arc<World>, states: &Self::States) {
+        P0::destroy(world, &states.0);
+        P1::destroy(world, &states.1);
+        P2::destroy(world, &states.2);
+    }
+}
+
+impl<P0: SystemParam, P1: SystemParam> ParamSet<(P0, P1)> {
+    /// Borrows the first parameter.
+    pub fn p0(&mut self) -> ParamSetItem<'_, P0> {
+        ParamSetItem {
+            param: P0::fetch::<Sealer>(&self.world, &self.state.0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Borrows the second parameter.
+    pub fn p1(&mut self) -> ParamSetItem<'_, P1> {
+        ParamSetItem {
+            param: P1::fetch::<Sealer>(&self.world, &self.state.1),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P0: SystemParam, P1: SystemParam, P2: SystemParam> ParamSet<(P0, P1, P2)> {
+    /// Borrows the first parameter.
+    pub fn p0(&mut self) -> ParamSetItem<'_, P0> {
+        ParamSetItem {
+            param: P0::fetch::<Sealer>(&self.world, &self.state.0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Borrows the second parameter.
+    pub fn p1(&mut self) -> ParamSetItem<'_, P1> {
+        ParamSetItem {
+            param: P1::fetch::<Sealer>(&self.world, &self.state.1),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Borrows the third parameter.
+    pub fn p2(&mut self) -> ParamSetItem<'_, P2> {
+        ParamSetItem {
+            param: P2::fetch::<Sealer>(&self.world, &self.state.2),
+            _marker: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use parking_lot::Mutex;
+
+use crate::{
+    scheduler::{SystemDescriptor, SystemParamDescriptor},
+    sealed, FnContainer, ParameterizedSystem, PinnedFut, System, SystemParam, SystemParams,
+    SystemReturnable, World,
+};
+
+/// The output of the previous system in a [pipe](PipeSystem::pipe).
+///
+/// This must be the first parameter of the receiving system. Adding a system that takes `In` by itself
+/// fails with [`EcsError::UnpipedInput`](crate::EcsError::UnpipedInput).
+///
+/// ```ignore
+/// fn parse_input(reader: EventReader<RawPacket>) -> Vec<Packet> {
+///     reader.read().map(Packet::parse).collect()
+/// }
+///
+/// fn apply_input(In(packets): In<Vec<Packet>>, query: Query<&mut Transform>) {
+///     // ...
+/// }
+///
+/// schedule.add_system(parse_input.pipe(apply_input));
+/// ```
+pub struct In<T>(pub T);
+
+impl<T: Send + Sync + 'static> SystemParam for In<T> {
+    /// Holds the input until the receiving system takes it.
+    type State = Mutex<Option<T>>;
+
+    fn descriptor() -> SystemParamDescriptor {
+        SystemParamDescriptor::In
+    }
+
+    fn fetch<S: sealed::Sealed>(_world: &Arc<World>, state: &Arc<Self::State>) -> Self {
+        let input = state
+            .lock()
+            .take()
+            .expect("In<T> can only be used by systems that receive piped input");
+
+        In(input)
+    }
+
+    fn state(_world: &Arc<World>) -> Arc<Self::State> {
+        Arc::new(Mutex::new(None))
+    }
+}
+
+/// Parameters of systems that can receive the output of another system, see [`In`].
+pub trait PipeInput<T>: SystemParams {
+    /// Stores the input so that it is passed to the system on its next run.
+    fn set_input(state: &Self::ArcState, input: T);
+}
+
+impl<T: Send + Sync + 'static> PipeInput<T> for In<T> {
+    fn set_input(state: &Self::ArcState, input: T) {
+        *state.lock() = Some(input);
+    }
+}
+
+impl<T: Send + Sync + 'static, P2: SystemParam> PipeInput<T> for (In<T>, P2) {
+    fn set_input(state: &Self::ArcState, input: T) {
+        *state.0.lock() = Some(input);
+    }
+}
+
+impl<T, P2, P3> PipeInput<T> for (In<T>, P2, P3)
+where
+    T: Send + Sync + 'static,
+    P2: SystemParam,
+    P3: SystemParam,
+{
+    fn set_input(state: &Self::ArcState, input: T) {
+        *state.0.lock() = Some(input);
+    }
+}
+
+/// The combined parameters of two piped systems.
+pub struct Piped<PA, PB>(PhantomData<fn() -> (PA, PB)>);
+
+impl<PA: SystemParams, PB: SystemParams> SystemParams for Piped<PA, PB> {
+    type ArcState = (PA::ArcState, PB::ArcState);
+
+    fn state(world: &Arc<World>) -> Self::ArcState {
+        (PA::state(world), PB::state(world))
+    }
+
+    fn descriptors() -> Vec<SystemParamDescriptor> {
+        vec![SystemParamDescriptor::Pipe(
+            PA::descriptors(),
+            PB::descriptors(),
+        )]
+    }
+
+    fn init(world: &Arc<World>, state: &Self::ArcState) {
+        PA::init(world, &state.0);
+        PB::init(world, &state.1);
+    }
+
+    fn destroy(world: &Arc<World>, state: &Self::ArcState) {
+        PA::destroy(world, &state.0);
+        PB::destroy(world, &state.1);
+    }
+}
+
+/// Two systems that run as one, where the output of the first is the input of the second.
+///
+/// Created by [`PipeSystem::pipe`].
+pub struct Pipe<A, B, RA> {
+    first: A,
+    second: B,
+    _marker: PhantomData<fn() -> RA>,
+}
+
+impl<A, B, PA, RA, PB, RB> ParameterizedSystem<Piped<PA, PB>, RB> for Pipe<A, B, RA>
+where
+    A: ParameterizedSystem<PA, RA>,
+    B: ParameterizedSystem<PB, RB>,
+    PA: SystemParams,
+    PB: PipeInput<RA>,
+{
+    fn call(&self, world: &Arc<World>, state: &<Piped<PA, PB> as SystemParams>::ArcState) -> RB {
+        // The parameters of the first system are released before the second one fetches its own.
+        let output = self.first.call(world, &state.0);
+        PB::set_input(&state.1, output);
+        self.second.call(world, &state.1)
+    }
+}
+
+/// Adds the [`pipe`](PipeSystem::pipe) combinator to systems.
+pub trait PipeSystem<P: SystemParams, R>: ParameterizedSystem<P, R> {
+    /// Passes the output of this system to `next`, which receives it through an [`In`] parameter.
+    ///
+    /// Both systems run as a single unit in the same executor slot. The scheduler accounts for the
+    /// combined access of both, but they are allowed to conflict with each other since they never run at the same time.
+    fn pipe<PB, RB, B>(self, next: B) -> Pipe<Self, B, R>
+    where
+        PB: PipeInput<R>,
+        B: ParameterizedSystem<PB, RB>,
+    {
+        Pipe {
+            first: self,
+            second: next,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P: SystemParams, R, F: ParameterizedSystem<P, R>> PipeSystem<P, R> for F {}
+
+unsafe impl<PA, PB, R, F> System for FnContainer<Piped<PA, PB>, R, F>
+where
+    PA: SystemParams,
+    PB: SystemParams,
+    R: SystemReturnable,
+    F: ParameterizedSystem<Piped<PA, PB>, R>,
+{
+    fn descriptor(&self) -> SystemDescriptor {
+        SystemDescriptor {
+            id: self.id,
+            deps: Piped::<PA, PB>::descriptors(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn call(&self, world: &Arc<World>) -> PinnedFut {
+        self.system.call(world, &self.state).into_future()
+    }
+
+    #[inline]
+    fn is_async(&self) -> bool {
+        R::IS_ASYNC
+    }
+
+    fn init(&self, world: &Arc<World>) {
+        Piped::<PA, PB>::init(world, &self.state);
+    }
+
+    fn destroy(&self, world: &Arc<World>) {
+        Piped::<PA, PB>::destroy(world, &self.state);
+    }
+}
@@ -8,23 +8,36 @@ use smallvec::SmallVec;
 
 use crate::{
     scheduler::{BorrowedTypeDescriptor, SystemParamDescriptor},
-    sealed, Component, EcsResult, Entity, EntityIter, FilterParams, SystemParam,
-    TypedStorage, World,
+    sealed, Component, Disabled, EcsResult, Entity, EntityId, EntityIter, EntityMut, EntityRef,
+    FilterParams, Relation, SystemParam, TypedStorage, World,
 };
 
 pub trait QueryParams {
     type Fetchable<'query>;
 
     const EXCLUSIVE: bool;
+    /// Whether these parameters can access arbitrary components of an entity, such as [`EntityMut`].
+    ///
+    /// The accessed components are not known in advance, so a query with these parameters requires
+    /// access to the entire world, just like [`ExclusiveWorld`](crate::ExclusiveWorld).
+    const WORLD: bool = false;
+
+    /// Whether queries with these parameters should also visit [`Disabled`](crate::Disabled) entities,
+    /// like a [`WithDisabled`](crate::WithDisabled) filter.
+    ///
+    /// Parameters borrowing the `Disabled` component only match disabled entities, so they have to include them.
+    fn include_disabled() -> bool {
+        false
+    }
 
     fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]>;
     fn type_id() -> TypeId {
         panic!("This QueryParams implementation does not require the `type_id` function")
     }
 
-    fn fetch<'w>(world: &'w World, entity: Entity) -> Option<Self::Fetchable<'w>>;
+    fn fetch<'w>(world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>>;
     /// Ensures that the entity has the requested components.
-    fn filter(entity: &Entity) -> bool;
+    fn filter(entity: &EntityRef) -> bool;
     /// Acquires the locks on the requested component storages.
     fn get_locks(world: &World) -> EcsResult<()>;
     /// Releases all previously acquired locks.
@@ -44,12 +57,91 @@ impl QueryParams for Entity {
         TypeId::of::<Self>()
     }
 
-    fn fetch<'w>(_world: &'w World, entity: Entity) -> Option<Self::Fetchable<'w>> {
-        Some(entity)
+    /// Creating an owned `Entity` clones the world handle. Prefer [`EntityRef`] or [`EntityId`]
+    /// unless the entity has to be stored.
+    fn fetch<'w>(_world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>> {
+        Some(entity.to_entity())
     }
 
     /// An entity query param needs no filtering as every entity can obviously produce an `Entity` type.
-    fn filter(_entity: &Entity) -> bool {
+    fn filter(_entity: &EntityRef) -> bool {
+        true
+    }
+
+    fn get_locks(_world: &World) -> EcsResult<()> {
+        Ok(()) /* Entities require no locks */
+    }
+    fn release_locks(_world: &World) { /* Entities require no locks. */
+    }
+}
+
+impl QueryParams for EntityId {
+    type Fetchable<'query> = EntityId;
+
+    const EXCLUSIVE: bool = false;
+
+    fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]> {
+        SmallVec::new()
+    }
+
+    fn fetch<'w>(_world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>> {
+        Some(entity.id())
+    }
+
+    fn filter(_entity: &EntityRef) -> bool {
+        true
+    }
+
+    fn get_locks(_world: &World) -> EcsResult<()> {
+        Ok(()) /* Entities require no locks */
+    }
+    fn release_locks(_world: &World) { /* Entities require no locks. */
+    }
+}
+
+impl QueryParams for EntityRef<'_> {
+    type Fetchable<'query> = EntityRef<'query>;
+
+    const EXCLUSIVE: bool = false;
+
+    fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]> {
+        SmallVec::new()
+    }
+
+    fn fetch<'w>(_world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>> {
+        Some(entity)
+    }
+
+    fn filter(_entity: &EntityRef) -> bool {
+        true
+    }
+
+    fn get_locks(_world: &World) -> EcsResult<()> {
+        Ok(()) /* Entities require no locks */
+    }
+    fn release_locks(_world: &World) { /* Entities require no locks. */
+    }
+}
+
+/// An `EntityMut` can mutate any component of the entity, so systems querying it run exclusively.
+impl QueryParams for EntityMut<'_> {
+    type Fetchable<'query> = EntityMut<'query>;
+
+    const EXCLUSIVE: bool = false;
+    const WORLD: bool = true;
+
+    fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]> {
+        SmallVec::new()
+    }
+
+    fn fetch<'w>(_world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>> {
+        Some(EntityMut {
+            world: entity.world,
+            id: entity.id,
+        })
+    }
+
+    fn filter(_entity: &EntityRef) -> bool {
         true
     }
 
@@ -65,13 +157,14 @@ impl<T: Component> QueryParams for &T {
 
     const EXCLUSIVE: bool = false;
 
+    fn include_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
+
     fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]> {
         let mut deps = SmallVec::new();
 
-        deps.push(BorrowedTypeDescriptor {
-            exclusive: Self::EXCLUSIVE,
-            type_id: TypeId::of::<T>(),
-        });
+        deps.push(BorrowedTypeDescriptor::of::<T>(Self::EXCLUSIVE));
 
         deps
     }
@@ -80,7 +173,7 @@ impl<T: Component> QueryParams for &T {
         TypeId::of::<T>()
     }
 
-    fn fetch<'w>(world: &'w World, entity: Entity) -> Option<Self::Fetchable<'w>> {
+    fn fetch<'w>(world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>> {
         debug_assert_eq!(
             TypeId::of::<&T>(),
             TypeId::of::<Self::Fetchable<'static>>(),
@@ -113,7 +206,7 @@ impl<T: Component> QueryParams for &T {
         Some(cast)
     }
 
-    fn filter(entity: &Entity) -> bool {
+    fn filter(entity: &EntityRef) -> bool {
         entity.has::<T>()
     }
 
@@ -160,13 +253,14 @@ impl<T: Component> QueryParams for &mut T {
 
     const EXCLUSIVE: bool = true;
 
+    fn include_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
+
     fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]> {
         let mut deps = SmallVec::new();
 
-        deps.push(BorrowedTypeDescriptor {
-            exclusive: Self::EXCLUSIVE,
-            type_id: TypeId::of::<T>(),
-        });
+        deps.push(BorrowedTypeDescriptor::of::<T>(Self::EXCLUSIVE));
 
         deps
     }
@@ -175,7 +269,7 @@ impl<T: Component> QueryParams for &mut T {
         TypeId::of::<T>()
     }
 
-    fn fetch<'w>(world: &'w World, entity: Entity) -> Option<Self::Fetchable<'w>> {
+    fn fetch<'w>(world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>> {
         debug_assert_eq!(
             TypeId::of::<&mut T>(),
             TypeId::of::<Self::Fetchable<'static>>(),
@@ -209,7 +303,7 @@ impl<T: Component> QueryParams for &mut T {
         Some(cast)
     }
 
-    fn filter(entity: &Entity) -> bool {
+    fn filter(entity: &EntityRef) -> bool {
         entity.has::<T>()
     }
 
@@ -255,31 +349,27 @@ impl<Q1: QueryParams, Q2: QueryParams> QueryParams for (Q1, Q2) {
     type Fetchable<'query> = (Q1::Fetchable<'query>, Q2::Fetchable<'query>);
 
     const EXCLUSIVE: bool = Q1::EXCLUSIVE || Q2::EXCLUSIVE;
+    const WORLD: bool = Q1::WORLD || Q2::WORLD;
 
-    fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]> {
-        let mut deps = SmallVec::new();
-
-        deps.push(BorrowedTypeDescriptor {
-            exclusive: Q1::EXCLUSIVE,
-            type_id: Q1::type_id(),
-        });
+    fn include_disabled() -> bool {
+        Q1::include_disabled() || Q2::include_disabled()
+    }
 
-        deps.push(BorrowedTypeDescriptor {
-            exclusive: Q2::EXCLUSIVE,
-            type_id: Q2::type_id(),
-        });
+    fn descriptor() -> SmallVec<[BorrowedTypeDescriptor; 3]> {
+        let mut deps = Q1::descriptor();
+        deps.extend(Q2::descriptor());
 
         deps
     }
 
-    fn fetch<'w>(world: &'w World, entity: Entity) -> Option<Self::Fetchable<'w>> {
-        let q1 = Q1::fetch(world, entity.clone())?;
+    fn fetch<'w>(world: &'w World, entity: EntityRef<'w>) -> Option<Self::Fetchable<'w>> {
+        let q1 = Q1::fetch(world, entity)?;
         let q2 = Q2::fetch(world, entity)?;
 
         Some((q1, q2))
     }
 
-    fn filter(entity: &Entity) -> bool {
+    fn filter(entity: &EntityRef) -> bool {
         Q1::filter(entity) && Q2::filter(entity)
     }
 
@@ -325,13 +415,45 @@ impl<Q: QueryParams, F: FilterParams> Query<Q, F> {
             _marker: PhantomData,
         })
     }
+
+    /// Iterates over the entities matching this query that have a relation of kind `R` pointing to `target`.
+    ///
+    /// This is a method rather than a filter since filters are types without any state and therefore
+    /// cannot hold the target entity. Entities that have a relation of kind `R` to any target can be
+    /// queried with `With<Related<R>>` or `&Related<R>` instead. Unlike those, this method uses the reverse
+    /// index of relations and only visits the related entities.
+    pub fn iter_related_to<R: Relation>(
+        &self,
+        target: EntityId,
+    ) -> impl Iterator<Item = Q::Fetchable<'_>> {
+        self.world
+            .relations
+            .sources::<R>(target)
+            .into_iter()
+            .filter_map(move |id| {
+                let entity = EntityRef {
+                    world: &self.world,
+                    id,
+                };
+
+                if entity.matches::<Q, F>() {
+                    Q::fetch(&self.world, entity)
+                } else {
+                    None
+                }
+            })
+    }
 }
 
 impl<Q: QueryParams, F: FilterParams> SystemParam for Query<Q, F> {
     type State = ();
 
     fn descriptor() -> SystemParamDescriptor {
-        SystemParamDescriptor::Query(Q::descriptor())
+        if Q::WORLD {
+            SystemParamDescriptor::World
+        } else {
+            SystemParamDescriptor::Query(Q::descriptor())
+        }
     }
 
     fn fetch<S: sealed::Sealed>(world: &Arc<World>, _state: &Arc<Self::State>) -> Self {
@@ -0,0 +1,175 @@
+use std::any::TypeId;
+use std::collections::HashSet;
+
+use dashmap::DashMap;
+
+use crate::{
+    Component, ComponentHooks, DeferredWorld, EcsError, EcsResult, Entity, EntityId, World,
+};
+
+/// A kind of relationship between two entities, such as `Targets` or `LeashedTo`.
+///
+/// Relations are stored on the source entity as a [`Related<R>`] component and can be looked up in
+/// both directions. When the target entity is despawned, all relations pointing to it are removed.
+///
+/// ```ignore
+/// struct LeashedTo { length: f32 }
+///
+/// impl Relation for LeashedTo {}
+///
+/// mob.relate(player.id(), LeashedTo { length: 10.0 });
+/// ```
+pub trait Relation: Send + Sync + 'static {}
+
+/// A relation of kind `R` from the entity holding this component to a target entity.
+///
+/// This component is created with [`Entity::relate`] and cannot be inserted directly.
+pub struct Related<R: Relation> {
+    pub(crate) target: EntityId,
+    relation: R,
+}
+
+impl<R: Relation> Related<R> {
+    /// The entity this relation points to.
+    pub fn target(&self) -> EntityId {
+        self.target
+    }
+
+    /// The data stored in the relation.
+    pub fn relation(&self) -> &R {
+        &self.relation
+    }
+
+    /// Mutable access to the data stored in the relation.
+    pub fn relation_mut(&mut self) -> &mut R {
+        &mut self.relation
+    }
+}
+
+impl<R: Relation> Component for Related<R> {
+    fn register_hooks(hooks: &mut ComponentHooks) {
+        hooks
+            .on_insert(relation_inserted::<R>)
+            .on_replace(relation_replaced::<R>);
+    }
+}
+
+/// Adds the relation to the reverse index.
+fn relation_inserted<R: Relation>(world: DeferredWorld, entity: EntityId) {
+    if let Ok(related) = world.get::<Related<R>>(entity) {
+        world
+            .world()
+            .relations
+            .insert(TypeId::of::<Related<R>>(), related.target, entity);
+    }
+}
+
+/// Removes the relation from the reverse index.
+fn relation_replaced<R: Relation>(world: DeferredWorld, entity: EntityId) {
+    if let Ok(related) = world.get::<Related<R>>(entity) {
+        world
+            .world()
+            .relations
+            .remove(TypeId::of::<Related<R>>(), related.target, entity);
+    }
+}
+
+/// Reverse index of all relations, mapping target entities to the entities that point to them.
+#[derive(Default)]
+pub(crate) struct Relations {
+    /// Maps a target to the type IDs of the relation components and their source entities.
+    pub(crate) targets: DashMap<EntityId, HashSet<(TypeId, EntityId)>>,
+}
+
+impl Relations {
+    pub fn clear(&self) {
+        self.targets.clear();
+    }
+
+    fn insert(&self, type_id: TypeId, target: EntityId, source: EntityId) {
+        self.targets
+            .entry(target)
+            .or_default()
+            .insert((type_id, source));
+    }
+
+    fn remove(&self, type_id: TypeId, target: EntityId, source: EntityId) {
+        if let Some(mut sources) = self.targets.get_mut(&target) {
+            sources.remove(&(type_id, source));
+        }
+
+        self.targets
+            .remove_if(&target, |_, sources| sources.is_empty());
+    }
+
+    /// Returns all entities that point to `target` through a relation of kind `R`.
+    pub fn sources<R: Relation>(&self, target: EntityId) -> Vec<EntityId> {
+        let type_id = TypeId::of::<Related<R>>();
+        self.targets
+            .get(&target)
+            .map(|sources| {
+                sources
+                    .iter()
+                    .filter(|(t, _)| *t == type_id)
+                    .map(|(_, source)| *source)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Removes all relations pointing to the given target. This is called when the target is despawned.
+    pub fn clear_target(&self, world: &World, target: EntityId) {
+        let Some((_, sources)) = self.targets.remove(&target) else {
+            return;
+        };
+
+        for (type_id, source) in sources {
+            world
+                .components
+                .remove_by_id(type_id, source)
+                .expect("Cannot remove relation, storage is locked.");
+        }
+    }
+}
+
+impl Entity {
+    /// Creates a relation of kind `R` from this entity to `target`, replacing any existing relation of this kind.
+    /// The actual change is only performed after all systems have completed running.
+    ///
+    /// If either entity has been despawned by then, no relation is created.
+    pub fn relate<R: Relation>(&self, target: EntityId, relation: R) {
+        let source = self.id;
+        self.world.scheduler.schedule_command(move |world| {
+            if !world.entities.contains(source) || !world.entities.contains(target) {
+                return;
+            }
+
+            world
+                .components
+                .insert(source, Related { target, relation })
+                .expect("Cannot insert relation, storage is locked.");
+        });
+    }
+
+    /// Removes the relation of kind `R` from this entity.
+    /// The actual change is only performed after all systems have completed running.
+    pub fn unrelate<R: Relation>(&self) {
+        self.remove::<Related<R>>();
+    }
+
+    /// Returns the target of this entity's relation of kind `R`, if it has one.
+    pub fn related<R: Relation>(&self) -> EcsResult<Option<EntityId>> {
+        match self.get::<Related<R>>() {
+            Ok(related) => Ok(Some(related.target)),
+            Err(EcsError::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl World {
+    /// Returns all entities that have a relation of kind `R` pointing to `target`.
+    pub fn related_to<R: Relation>(&self, target: EntityId) -> Vec<EntityId> {
+        self.relations.sources::<R>(target)
+    }
+}
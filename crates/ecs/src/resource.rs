@@ -12,8 +12,9 @@ use std::{
 use dashmap::DashMap;
 
 use crate::{
-    scheduler::SystemParamDescriptor, sealed, EcsError, EcsResult, PersistentLock, SystemParam,
-    World,
+    component::lock_all,
+    scheduler::{BorrowedTypeDescriptor, SystemParamDescriptor},
+    sealed, EcsError, EcsResult, PersistentLock, SystemParam, World,
 };
 
 struct ResourceSingleton<R: Resource> {
@@ -53,6 +54,7 @@ impl<R: Resource> ResourceSingleton<R> {
 trait ResourceHolder: Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn lock(&self) -> &PersistentLock;
 }
 
 impl<R: Resource> ResourceHolder for ResourceSingleton<R> {
@@ -63,6 +65,10 @@ impl<R: Resource> ResourceHolder for ResourceSingleton<R> {
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+
+    fn lock(&self) -> &PersistentLock {
+        &self.lock
+    }
 }
 
 #[derive(Default)]
@@ -85,6 +91,27 @@ impl Resources {
         );
     }
 
+    /// Returns whether a resource of type `R` exists.
+    pub fn contains<R: Resource>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<R>())
+    }
+
+    /// Write-locks all resources in preparation of [`clear_locked`](Self::clear_locked).
+    ///
+    /// This returns [`EcsError::StorageLocked`] and leaves all resources unlocked if any of them is currently borrowed.
+    pub(crate) fn lock_all(&self) -> EcsResult<()> {
+        lock_all(self.map.iter(), |holder| holder.value().lock())
+    }
+
+    /// Drops all resources.
+    ///
+    /// # Safety
+    ///
+    /// All resources must have been locked by [`lock_all`](Self::lock_all), so that nobody holds a reference to them.
+    pub(crate) unsafe fn clear_locked(&self) {
+        self.map.clear();
+    }
+
     /// # Safety:
     ///
     /// Aliasing invariants must be upheld manually.
@@ -188,7 +215,7 @@ impl<R: Resource> SystemParam for Res<R> {
     type State = ();
 
     fn descriptor() -> SystemParamDescriptor {
-        SystemParamDescriptor::Res(TypeId::of::<R>())
+        SystemParamDescriptor::Res(BorrowedTypeDescriptor::of::<R>(false))
     }
 
     fn fetch<S: sealed::Sealed>(world: &Arc<World>, _state: &Arc<Self::State>) -> Self {
@@ -204,6 +231,26 @@ impl<R: Resource> SystemParam for Res<R> {
     }
 }
 
+/// Accesses a resource that might not exist.
+impl<R: Resource> SystemParam for Option<Res<R>> {
+    type State = ();
+
+    fn descriptor() -> SystemParamDescriptor {
+        SystemParamDescriptor::Res(BorrowedTypeDescriptor::of::<R>(false))
+    }
+
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, state: &Arc<Self::State>) -> Self {
+        world
+            .resources
+            .contains::<R>()
+            .then(|| Res::fetch::<S>(world, state))
+    }
+
+    fn state(_world: &Arc<World>) -> Arc<Self::State> {
+        Arc::new(())
+    }
+}
+
 impl<R: Resource> Deref for Res<R> {
     type Target = R;
 
@@ -240,7 +287,7 @@ impl<R: Resource> SystemParam for ResMut<R> {
     type State = ();
 
     fn descriptor() -> SystemParamDescriptor {
-        SystemParamDescriptor::ResMut(TypeId::of::<R>())
+        SystemParamDescriptor::ResMut(BorrowedTypeDescriptor::of::<R>(true))
     }
 
     fn fetch<S: sealed::Sealed>(world: &Arc<World>, _state: &Arc<Self::State>) -> Self {
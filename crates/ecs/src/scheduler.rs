@@ -1,20 +1,42 @@
 use crate::{
-    AsyncSystem, EntityId, FnContainer, ParameterizedSystem, System, SystemParams,
-    SystemReturnable, World,
+    AsyncSystem, Background, BackgroundSystem, Condition, EcsError, EcsResult, EntityId, Fallible,
+    FetchParams, FnContainer, ParameterizedSystem, SpawnBundle, System, SystemError, SystemParams,
+    SystemResult, SystemReturnable, World,
 };
 use dashmap::{DashMap, DashSet};
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use nohash_hasher::BuildNoHashHasher;
-use smallvec::SmallVec;
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use parking_lot::Mutex;
+use smallvec::{smallvec, SmallVec};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
 use std::{any::TypeId, marker::PhantomData};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BorrowedTypeDescriptor {
     pub exclusive: bool,
     pub type_id: TypeId,
+    /// The name of the borrowed type, used in diagnostics.
+    pub type_name: &'static str,
+}
+
+impl BorrowedTypeDescriptor {
+    /// Describes a borrow of `T`.
+    pub fn of<T: 'static>(exclusive: bool) -> Self {
+        Self {
+            exclusive,
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// Returns whether both borrows are of the same type and at least one of them is exclusive.
+    pub fn conflicts_with(&self, other: &BorrowedTypeDescriptor) -> bool {
+        self.type_id == other.type_id && (self.exclusive || other.exclusive)
+    }
 }
 
 /// System parameters that the scheduler needs to account for.
@@ -28,8 +50,156 @@ pub enum SystemParamDescriptor {
     EventWriter,
     State,
     Query(SmallVec<[BorrowedTypeDescriptor; 3]>),
-    Res(TypeId),
-    ResMut(TypeId),
+    Res(BorrowedTypeDescriptor),
+    ResMut(BorrowedTypeDescriptor),
+    /// The parameters of a [`ParamSet`](crate::ParamSet), which are never borrowed at the same time.
+    ParamSet(Vec<SystemParamDescriptor>),
+    /// Access to the entire world, see [`ExclusiveWorld`](crate::ExclusiveWorld).
+    World,
+    /// The parameters of two [piped](crate::PipeSystem::pipe) systems, which are fetched one after the other.
+    Pipe(Vec<SystemParamDescriptor>, Vec<SystemParamDescriptor>),
+    /// The output of the previous system in a pipe, see [`In`](crate::In).
+    ///
+    /// This is only valid as the first parameter of the receiving system of a [`Pipe`](Self::Pipe).
+    In,
+}
+
+impl SystemParamDescriptor {
+    /// The component storages borrowed by this parameter.
+    fn component_borrows(&self) -> Vec<&BorrowedTypeDescriptor> {
+        match self {
+            Self::Query(borrows) => borrows.iter().collect(),
+            Self::ParamSet(params) => params.iter().flat_map(|p| p.component_borrows()).collect(),
+            Self::Pipe(first, second) => first
+                .iter()
+                .chain(second)
+                .flat_map(|p| p.component_borrows())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The resources borrowed by this parameter.
+    fn resource_borrows(&self) -> Vec<&BorrowedTypeDescriptor> {
+        match self {
+            Self::Res(borrow) | Self::ResMut(borrow) => vec![borrow],
+            Self::ParamSet(params) => params.iter().flat_map(|p| p.resource_borrows()).collect(),
+            Self::Pipe(first, second) => first
+                .iter()
+                .chain(second)
+                .flat_map(|p| p.resource_borrows())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Collects the names of the types that both this parameter and `other` borrow, with at least one of them
+    /// requiring exclusive access.
+    fn conflicting_types(&self, other: &SystemParamDescriptor, out: &mut Vec<&'static str>) {
+        // Exclusive world access conflicts with every other borrow.
+        match (self, other) {
+            (Self::World, Self::World) => {
+                out.push(std::any::type_name::<World>());
+                return;
+            }
+            (Self::World, param) | (param, Self::World) => {
+                let borrows = param.component_borrows().into_iter();
+                out.extend(borrows.chain(param.resource_borrows()).map(|b| b.type_name));
+                return;
+            }
+            _ => {}
+        }
+
+        let pairs = [
+            (self.component_borrows(), other.component_borrows()),
+            (self.resource_borrows(), other.resource_borrows()),
+        ];
+
+        for (ours, theirs) in pairs {
+            for a in &ours {
+                if theirs.iter().any(|b| a.conflicts_with(b)) {
+                    out.push(a.type_name);
+                }
+            }
+        }
+    }
+
+    /// Collects the names of the types that this parameter borrows in conflicting ways by itself.
+    ///
+    /// The parameters of a [`ParamSet`](crate::ParamSet) are allowed to conflict with each other.
+    fn internal_conflicts(&self, out: &mut Vec<&'static str>) {
+        match self {
+            Self::ParamSet(params) => {
+                for param in params {
+                    param.internal_conflicts(out);
+                }
+
+                return;
+            }
+            // Piped systems never hold their parameters at the same time.
+            Self::Pipe(first, second) => {
+                params_conflicts(first, out);
+                params_conflicts(second, out);
+
+                return;
+            }
+            _ => {}
+        }
+
+        for borrows in [self.component_borrows(), self.resource_borrows()] {
+            for (i, a) in borrows.iter().enumerate() {
+                if borrows[..i].iter().any(|b| a.conflicts_with(b)) {
+                    out.push(a.type_name);
+                }
+            }
+        }
+    }
+
+    /// Returns whether this parameter and `other` access the same data with at least one of them
+    /// requiring exclusive access.
+    pub fn conflicts_with(&self, other: &SystemParamDescriptor) -> bool {
+        let mut conflicts = Vec::new();
+        self.conflicting_types(other, &mut conflicts);
+
+        !conflicts.is_empty()
+    }
+
+    /// Returns whether this parameter requires access to the entire world.
+    fn is_world(&self) -> bool {
+        match self {
+            Self::World => true,
+            Self::ParamSet(params) => params.iter().any(Self::is_world),
+            Self::Pipe(first, second) => first.iter().chain(second).any(Self::is_world),
+            _ => false,
+        }
+    }
+}
+
+/// Returns whether the parameters of a single system contain an [`In`](crate::In) that is not fed by a pipe.
+fn params_unpiped_input(params: &[SystemParamDescriptor]) -> bool {
+    params.iter().any(|param| match param {
+        SystemParamDescriptor::In => true,
+        SystemParamDescriptor::ParamSet(params) => params_unpiped_input(params),
+        SystemParamDescriptor::Pipe(first, second) => {
+            let rest = match second.split_first() {
+                Some((SystemParamDescriptor::In, rest)) => rest,
+                _ => second,
+            };
+
+            params_unpiped_input(first) || params_unpiped_input(rest)
+        }
+        _ => false,
+    })
+}
+
+/// Collects the names of the types that the parameters of a single system borrow in conflicting ways.
+fn params_conflicts(params: &[SystemParamDescriptor], out: &mut Vec<&'static str>) {
+    for (i, param) in params.iter().enumerate() {
+        param.internal_conflicts(out);
+        for other in &params[..i] {
+            param.conflicting_types(other, out);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -38,7 +208,54 @@ pub struct SystemDescriptor {
     pub deps: Vec<SystemParamDescriptor>,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+impl SystemDescriptor {
+    /// Returns whether this system only requires shared access to components and resources.
+    pub fn is_read_only(&self) -> bool {
+        !self.is_exclusive()
+            && self.deps.iter().all(|dep| {
+                dep.component_borrows().iter().all(|b| !b.exclusive)
+                    && dep.resource_borrows().iter().all(|b| !b.exclusive)
+            })
+    }
+
+    /// Returns whether this system and `other` cannot safely run at the same time.
+    pub fn conflicts_with(&self, other: &SystemDescriptor) -> bool {
+        self.is_exclusive()
+            || other.is_exclusive()
+            || self
+                .deps
+                .iter()
+                .any(|a| other.deps.iter().any(|b| a.conflicts_with(b)))
+    }
+
+    /// Returns whether this system requires access to the entire world, see [`ExclusiveWorld`](crate::ExclusiveWorld).
+    pub fn is_exclusive(&self) -> bool {
+        self.deps.iter().any(SystemParamDescriptor::is_world)
+    }
+
+    /// Returns the names of the types that this system itself borrows in conflicting ways,
+    /// such as a component that is queried both mutably and immutably.
+    ///
+    /// Such a system would fail to acquire its own parameters every time it runs.
+    pub fn access_conflicts(&self) -> Vec<&'static str> {
+        let mut conflicts = Vec::new();
+        params_conflicts(&self.deps, &mut conflicts);
+
+        conflicts.sort_unstable();
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// Returns whether this system takes an [`In`](crate::In) parameter without receiving the output of
+    /// another system through a [pipe](crate::PipeSystem::pipe).
+    ///
+    /// Such a system would have no input to fetch every time it runs.
+    pub fn has_unpiped_input(&self) -> bool {
+        params_unpiped_input(&self.deps)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 struct GraphEdge<T: PartialEq + Copy> {
     pub from: T,
     pub to: T,
@@ -50,12 +267,40 @@ impl<T: PartialEq + Copy> From<(T, T)> for GraphEdge<T> {
     }
 }
 
-struct OptimizedGraph {}
+impl<T: PartialEq + Copy> GraphEdge<T> {
+    /// Returns the other end of this edge if it touches `node`.
+    fn neighbour(&self, node: T) -> Option<T> {
+        if self.from == node {
+            Some(self.to)
+        } else if self.to == node {
+            Some(self.from)
+        } else {
+            None
+        }
+    }
+}
 
+/// An execution plan for a schedule.
+///
+/// Systems are grouped into batches that are run one after another.
+/// The systems within a single batch have no conflicting access and no ordering constraints between them,
+/// and can therefore run concurrently.
+#[derive(Debug, Default)]
+pub(crate) struct OptimizedGraph {
+    /// All systems in an order that satisfies the ordering constraints.
+    pub(crate) order: Vec<SystemId>,
+    pub(crate) batches: Vec<Vec<SystemId>>,
+}
+
+/// Graph with a node for every system.
+///
+/// Conflict edges connect systems that cannot run at the same time, while dependency edges
+/// point from a system to a system that must run after it.
 #[derive(Default)]
 struct ScheduleGraph {
     nodes: Vec<SystemId>,
     edges: Vec<GraphEdge<SystemId>>,
+    dependencies: Vec<GraphEdge<SystemId>>,
 }
 
 impl ScheduleGraph {
@@ -63,6 +308,22 @@ impl ScheduleGraph {
         Self::default()
     }
 
+    /// Builds the conflict graph for the given system descriptors.
+    pub fn from_descriptors(descriptors: &[SystemDescriptor]) -> Self {
+        let mut graph = Self::new();
+        for (i, system) in descriptors.iter().enumerate() {
+            graph.add_node(SystemId(system.id));
+
+            for other in &descriptors[..i] {
+                if system.conflicts_with(other) {
+                    graph.add_edge((SystemId(other.id), SystemId(system.id)));
+                }
+            }
+        }
+
+        graph
+    }
+
     pub fn add_node(&mut self, node: SystemId) {
         self.nodes.push(node);
     }
@@ -74,25 +335,414 @@ impl ScheduleGraph {
         self.edges.push(edge.into());
     }
 
-    pub fn remove_edge<I>(&mut self, edge: I)
+    /// Adds a constraint that `edge.from` must run before `edge.to`.
+    pub fn add_dependency<I>(&mut self, edge: I)
     where
         I: Into<GraphEdge<SystemId>>,
     {
-        let edge = edge.into();
-        self.edges.retain(|x| *x != edge);
+        self.dependencies.push(edge.into());
     }
 
-    pub fn optimize(self) {
-        let mut timeslots = vec![-1; self.nodes.len()];
-        let mut available = vec![true; self.nodes.len()];
+    /// Sorts the nodes so that every node comes after all of its dependencies.
+    ///
+    /// Nodes without constraints between them keep the order they were added in.
+    /// If the dependencies contain a cycle, the nodes forming it are returned instead.
+    fn topological_order(&self) -> Result<Vec<SystemId>, Vec<SystemId>> {
+        let index = |node: SystemId| self.nodes.iter().position(|n| *n == node).unwrap();
+
+        let mut in_degree = vec![0usize; self.nodes.len()];
+        for edge in &self.dependencies {
+            in_degree[index(edge.to)] += 1;
+        }
+
+        let mut ready: BTreeSet<usize> = (0..self.nodes.len())
+            .filter(|i| in_degree[*i] == 0)
+            .collect();
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(next) = ready.pop_first() {
+            let node = self.nodes[next];
+            order.push(node);
+
+            for edge in self.dependencies.iter().filter(|e| e.from == node) {
+                let to = index(edge.to);
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.insert(to);
+                }
+            }
+        }
+
+        if order.len() == self.nodes.len() {
+            return Ok(order);
+        }
 
-        for node in self.nodes {}
+        // Every node that is left has a dependency that is left as well.
+        // Walking these dependencies backwards must therefore eventually revisit a node.
+        let mut path: Vec<usize> = vec![in_degree.iter().position(|d| *d > 0).unwrap()];
+        loop {
+            let node = self.nodes[*path.last().unwrap()];
+            let prev = self
+                .dependencies
+                .iter()
+                .filter(|e| e.to == node)
+                .map(|e| index(e.from))
+                .find(|i| in_degree[*i] > 0)
+                .unwrap();
+
+            if let Some(start) = path.iter().position(|i| *i == prev) {
+                return Err(path[start..].iter().rev().map(|i| self.nodes[*i]).collect());
+            }
+
+            path.push(prev);
+        }
+    }
+
+    /// Colours the graph so that no two conflicting systems end up in the same batch,
+    /// while every system runs in a later batch than the systems it depends on.
+    ///
+    /// This visits the nodes in topological order and greedily assigns every node the first timeslot
+    /// after its dependencies that none of its conflicting neighbours occupy.
+    pub fn optimize(self) -> Result<OptimizedGraph, Vec<SystemId>> {
+        let order = self.topological_order()?;
+
+        let mut timeslots: HashMap<SystemId, usize> = HashMap::new();
+        let mut batches: Vec<Vec<SystemId>> = Vec::new();
+
+        for node in &order {
+            let earliest = self
+                .dependencies
+                .iter()
+                .filter(|edge| edge.to == *node)
+                .map(|edge| timeslots[&edge.from] + 1)
+                .max()
+                .unwrap_or(0);
+
+            let mut available = vec![true; batches.len()];
+            for neighbour in self.edges.iter().filter_map(|edge| edge.neighbour(*node)) {
+                if let Some(slot) = timeslots.get(&neighbour) {
+                    available[*slot] = false;
+                }
+            }
+
+            let slot = match (earliest..batches.len()).find(|slot| available[*slot]) {
+                Some(slot) => slot,
+                None => {
+                    batches.resize_with(batches.len().max(earliest) + 1, Vec::new);
+                    batches.len() - 1
+                }
+            };
+
+            timeslots.insert(*node, slot);
+            batches[slot].push(*node);
+        }
+
+        Ok(OptimizedGraph { order, batches })
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SystemId(usize);
 
+/// Identifies a system registered with [`World::register_system`].
+///
+/// These systems do not belong to a schedule, so their IDs are a separate type from [`SystemId`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OneShotSystemId(usize);
+
+/// Refers to systems in ordering constraints.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SystemTarget {
+    /// A single system.
+    Id(SystemId),
+    /// All systems in the given set, including those in nested sets. See [`SystemConfig::in_set`].
+    Set(&'static str),
+}
+
+impl From<SystemId> for SystemTarget {
+    fn from(id: SystemId) -> Self {
+        Self::Id(id)
+    }
+}
+
+impl From<&'static str> for SystemTarget {
+    fn from(set: &'static str) -> Self {
+        Self::Set(set)
+    }
+}
+
+/// A system together with its configuration.
+struct ScheduledSystem {
+    system: Arc<dyn System>,
+    /// Whether the system requires access to the entire world.
+    exclusive: bool,
+    /// Set by [`Schedule::set_enabled`] or by [`ErrorHandler::Disable`] when the system fails.
+    disabled: bool,
+    conditions: Vec<Box<dyn Condition>>,
+    sets: SmallVec<[&'static str; 2]>,
+    before: Vec<SystemTarget>,
+    after: Vec<SystemTarget>,
+}
+
+impl ScheduledSystem {
+    fn new(system: Arc<dyn System>, exclusive: bool) -> Self {
+        Self {
+            system,
+            exclusive,
+            disabled: false,
+            conditions: Vec::new(),
+            sets: SmallVec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+}
+
+/// Identifies a system that returned an error, see [`ErrorHandler`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SystemErrorContext {
+    pub id: SystemId,
+    /// The name of the system's function.
+    pub name: &'static str,
+}
+
+/// Decides what a [`Schedule`] does with the errors returned by its systems.
+///
+/// ```ignore
+/// schedule.set_error_handler(ErrorHandler::custom(|ctx, err| {
+///     eprintln!("{} failed: {err}", ctx.name);
+/// }));
+/// ```
+#[derive(Clone, Default)]
+pub enum ErrorHandler {
+    /// Prints the error to stderr and keeps running the system.
+    #[default]
+    Log,
+    /// Panics with the error.
+    Panic,
+    /// Prints the error to stderr and stops running the system in future runs.
+    Disable,
+    /// Passes the error to a closure.
+    Custom(Arc<dyn Fn(SystemErrorContext, SystemError) + Send + Sync>),
+}
+
+impl ErrorHandler {
+    /// Creates a handler that passes errors to the given closure.
+    pub fn custom<F>(handler: F) -> Self
+    where
+        F: Fn(SystemErrorContext, SystemError) + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(handler))
+    }
+}
+
+/// The shared configuration of a named group of systems.
+#[derive(Default)]
+struct SystemSet {
+    parents: SmallVec<[&'static str; 2]>,
+    before: Vec<SystemTarget>,
+    after: Vec<SystemTarget>,
+    conditions: Vec<Box<dyn Condition>>,
+    disabled: bool,
+}
+
+/// Configures a system that has just been added to a [`Schedule`].
+///
+/// ```ignore
+/// let detection = schedule.add_system(detection).in_set("combat").id();
+/// schedule.add_system(execution).after(detection);
+/// schedule.add_system(reward).after("combat");
+/// ```
+pub struct SystemConfig<'s, K: ExecutorKind> {
+    id: SystemId,
+    schedule: &'s mut Schedule<K>,
+}
+
+impl<'s, K: ExecutorKind> SystemConfig<'s, K> {
+    /// The ID of the configured system.
+    pub fn id(&self) -> SystemId {
+        self.id
+    }
+
+    fn system(&mut self) -> &mut ScheduledSystem {
+        self.schedule.systems.get_mut(&self.id.0).unwrap()
+    }
+
+    /// Runs this system before the given systems.
+    pub fn before(mut self, target: impl Into<SystemTarget>) -> Self {
+        self.system().before.push(target.into());
+        self
+    }
+
+    /// Runs this system after the given systems.
+    pub fn after(mut self, target: impl Into<SystemTarget>) -> Self {
+        self.system().after.push(target.into());
+        self
+    }
+
+    /// Only runs this system when `condition` returns `true`.
+    ///
+    /// Conditions are evaluated by the executor right before the system would run, in the order they were added.
+    /// When one of them returns `false`, the remaining ones are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the condition requires mutable access to a component or resource.
+    pub fn run_if<P, F>(mut self, condition: F) -> Self
+    where
+        P: SystemParams + 'static,
+        F: ParameterizedSystem<P, bool> + 'static,
+        FnContainer<P, bool, F>: Condition,
+    {
+        let condition = self.schedule.init_condition(condition);
+        self.system().conditions.push(condition);
+        self
+    }
+
+    /// Adds this system to a set. The system inherits the ordering, run conditions and enabled state of the set,
+    /// and other systems can be ordered relative to the set as a whole.
+    pub fn in_set(mut self, set: &'static str) -> Self {
+        self.system().sets.push(set);
+        self
+    }
+}
+
+impl<K: ExecutorKind> From<SystemConfig<'_, K>> for SystemId {
+    fn from(config: SystemConfig<'_, K>) -> Self {
+        config.id
+    }
+}
+
+/// Configures a set of systems, see [`Schedule::configure_set`].
+///
+/// ```ignore
+/// schedule.configure_set("physics").after("input").run_if(not_paused);
+/// schedule.configure_set("collisions").in_set("physics");
+/// schedule.add_system(resolve_collisions).in_set("collisions");
+/// ```
+pub struct SetConfig<'s, K: ExecutorKind> {
+    set: &'static str,
+    schedule: &'s mut Schedule<K>,
+}
+
+impl<'s, K: ExecutorKind> SetConfig<'s, K> {
+    fn set(&mut self) -> &mut SystemSet {
+        self.schedule.sets.entry(self.set).or_default()
+    }
+
+    /// Runs all systems in this set before the given systems.
+    pub fn before(mut self, target: impl Into<SystemTarget>) -> Self {
+        self.set().before.push(target.into());
+        self
+    }
+
+    /// Runs all systems in this set after the given systems.
+    pub fn after(mut self, target: impl Into<SystemTarget>) -> Self {
+        self.set().after.push(target.into());
+        self
+    }
+
+    /// Nests this set inside of `parent`, making all of its systems part of the parent as well.
+    pub fn in_set(mut self, parent: &'static str) -> Self {
+        self.set().parents.push(parent);
+        self
+    }
+
+    /// Only runs the systems in this set when `condition` returns `true`.
+    ///
+    /// The condition is evaluated at most once per run of the schedule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the condition requires mutable access to a component or resource.
+    pub fn run_if<P, F>(mut self, condition: F) -> Self
+    where
+        P: SystemParams + 'static,
+        F: ParameterizedSystem<P, bool> + 'static,
+        FnContainer<P, bool, F>: Condition,
+    {
+        let condition = self.schedule.init_condition(condition);
+        self.set().conditions.push(condition);
+        self
+    }
+
+    /// Enables or disables all systems in this set.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.set().disabled = !enabled;
+        self
+    }
+}
+
+/// A group of systems that can be added to a schedule at once, see [`Schedule::add_systems`].
+///
+/// This is implemented for tuples of systems.
+pub trait IntoSystems<Marker>: Sized {
+    /// Adds the systems to the schedule, returning their IDs.
+    fn add_to<K: ExecutorKind>(self, schedule: &mut Schedule<K>) -> Vec<SystemId>;
+
+    /// Runs the systems one after another, in the order they are listed.
+    fn chain(self) -> Chain<Self> {
+        Chain(self)
+    }
+}
+
+/// Systems that run one after another, created with [`IntoSystems::chain`].
+pub struct Chain<T>(T);
+
+impl<M, T: IntoSystems<M>> IntoSystems<M> for Chain<T> {
+    fn add_to<K: ExecutorKind>(self, schedule: &mut Schedule<K>) -> Vec<SystemId> {
+        let ids = self.0.add_to(schedule);
+        for pair in ids.windows(2) {
+            schedule.configure(pair[1]).after(pair[0]);
+        }
+
+        ids
+    }
+}
+
+impl<P0, R0, S0, P1, R1, S1> IntoSystems<(P0, R0, P1, R1)> for (S0, S1)
+where
+    P0: SystemParams + 'static,
+    R0: SystemReturnable + 'static,
+    S0: ParameterizedSystem<P0, R0> + 'static,
+    FnContainer<P0, R0, S0>: System,
+    P1: SystemParams + 'static,
+    R1: SystemReturnable + 'static,
+    S1: ParameterizedSystem<P1, R1> + 'static,
+    FnContainer<P1, R1, S1>: System,
+{
+    fn add_to<K: ExecutorKind>(self, schedule: &mut Schedule<K>) -> Vec<SystemId> {
+        vec![
+            schedule.add_system(self.0).id(),
+            schedule.add_system(self.1).id(),
+        ]
+    }
+}
+
+impl<P0, R0, S0, P1, R1, S1, P2, R2, S2> IntoSystems<(P0, R0, P1, R1, P2, R2)> for (S0, S1, S2)
+where
+    P0: SystemParams + 'static,
+    R0: SystemReturnable + 'static,
+    S0: ParameterizedSystem<P0, R0> + 'static,
+    FnContainer<P0, R0, S0>: System,
+    P1: SystemParams + 'static,
+    R1: SystemReturnable + 'static,
+    S1: ParameterizedSystem<P1, R1> + 'static,
+    FnContainer<P1, R1, S1>: System,
+    P2: SystemParams + 'static,
+    R2: SystemReturnable + 'static,
+    S2: ParameterizedSystem<P2, R2> + 'static,
+    FnContainer<P2, R2, S2>: System,
+{
+    fn add_to<K: ExecutorKind>(self, schedule: &mut Schedule<K>) -> Vec<SystemId> {
+        vec![
+            schedule.add_system(self.0).id(),
+            schedule.add_system(self.1).id(),
+            schedule.add_system(self.2).id(),
+        ]
+    }
+}
+
 pub trait ExecutorKind {}
 
 pub enum SingleThreadedExecutor {}
@@ -104,112 +754,907 @@ pub enum MultiThreadedExecutor {}
 impl ExecutorKind for MultiThreadedExecutor {}
 
 pub struct Schedule<K: ExecutorKind> {
-    world: Arc<World>,
+    /// Weak to prevent a reference cycle for the schedules owned by the world itself.
+    world: Weak<World>,
     next_id: usize,
-    systems: HashMap<usize, Arc<dyn System>, BuildNoHashHasher<usize>>,
+    systems: HashMap<usize, ScheduledSystem, BuildNoHashHasher<usize>>,
+    sets: HashMap<&'static str, SystemSet>,
+    /// Cached execution plan, invalidated whenever systems are added, removed or reconfigured.
+    plan: Option<OptimizedGraph>,
+    error_handler: ErrorHandler,
     _marker: PhantomData<K>,
 }
 
+impl<K: ExecutorKind> Drop for Schedule<K> {
+    /// Destroys the remaining systems, so that for example their event readers stop being counted.
+    /// If the world has already been dropped, there is nothing left to unregister from.
+    fn drop(&mut self) {
+        if let Some(world) = self.world.upgrade() {
+            self.clear(&world);
+        }
+    }
+}
+
 impl<K: ExecutorKind> Schedule<K> {
     pub fn new(world: &Arc<World>) -> Self {
+        Self::from_weak(Arc::downgrade(world))
+    }
+
+    fn from_weak(world: Weak<World>) -> Self {
         Self {
             next_id: 0,
             systems: HashMap::with_hasher(BuildNoHashHasher::default()),
-            world: Arc::clone(world),
+            sets: HashMap::new(),
+            plan: None,
+            error_handler: ErrorHandler::default(),
+            world,
             _marker: PhantomData,
         }
     }
 
-    pub fn add_system<P, R, S>(&mut self, system: S) -> SystemId
+    /// Returns the world this schedule runs on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the world has already been dropped.
+    fn world(&self) -> Arc<World> {
+        self.world
+            .upgrade()
+            .expect("Cannot use schedule, its world has been dropped.")
+    }
+
+    /// Returns whether this schedule contains no systems.
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    /// Returns the number of systems in this schedule.
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    /// Destroys and drops all systems.
+    ///
+    /// This also happens when the schedule is dropped while its world is still alive.
+    pub(crate) fn clear(&mut self, world: &Arc<World>) {
+        self.plan = None;
+        for (_, scheduled) in self.systems.drain() {
+            scheduled.system.destroy(world);
+            for condition in scheduled.conditions {
+                condition.destroy(world);
+            }
+        }
+
+        for (_, set) in self.sets.drain() {
+            for condition in set.conditions {
+                condition.destroy(world);
+            }
+        }
+    }
+
+    /// Adds a system to the schedule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system borrows the same data in conflicting ways or takes piped input outside of a pipe.
+    /// See [`try_add_system`](Self::try_add_system) for a fallible version.
+    pub fn add_system<P, R, S>(&mut self, system: S) -> SystemConfig<'_, K>
+    where
+        P: SystemParams + 'static,
+        R: SystemReturnable + 'static,
+        S: ParameterizedSystem<P, R> + 'static,
+        FnContainer<P, R, S>: System,
+    {
+        match self.try_add_system(system) {
+            Ok(config) => config,
+            Err(err) => panic!("Cannot add system: {err}"),
+        }
+    }
+
+    /// Adds a system to the schedule.
+    ///
+    /// This returns [`EcsError::ConflictingAccess`] if the system borrows the same data in conflicting ways,
+    /// such as querying a component both mutably and immutably, or requesting both [`Res`](crate::Res) and
+    /// [`ResMut`](crate::ResMut) of the same resource. Such a system could never acquire its parameters.
+    /// Likewise, it returns [`EcsError::UnpipedInput`] if the system takes an [`In`](crate::In) parameter
+    /// without being the receiving side of a [pipe](crate::PipeSystem::pipe).
+    pub fn try_add_system<P, R, S>(&mut self, system: S) -> EcsResult<SystemConfig<'_, K>>
     where
         P: SystemParams + 'static,
         R: SystemReturnable + 'static,
         S: ParameterizedSystem<P, R> + 'static,
         FnContainer<P, R, S>: System,
     {
+        let world = self.world();
         let system_id = self.next_id;
         self.next_id += 1;
 
-        let contained = Arc::new(system.into_container(system_id, &self.world));
-        contained.init(&self.world);
-
-        dbg!(contained.descriptor());
+        let contained = Arc::new(system.into_container(system_id, &world));
+        self.insert_system(&world, contained)
+    }
 
-        self.systems.insert(system_id, contained);
-        SystemId(system_id)
+    /// Adds an async system to the schedule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system borrows the same data in conflicting ways. See [`try_add_async_system`](Self::try_add_async_system)
+    /// for a fallible version.
+    pub fn add_async_system<P, S>(&mut self, system: S) -> SystemConfig<'_, K>
+    where
+        P: SystemParams + 'static,
+        S: AsyncSystem<P>,
+    {
+        match self.try_add_async_system(system) {
+            Ok(config) => config,
+            Err(err) => panic!("Cannot add system: {err}"),
+        }
     }
 
-    pub fn add_async_system<P, S>(&mut self, system: S) -> SystemId
+    /// Adds an async system to the schedule, see [`try_add_system`](Self::try_add_system).
+    pub fn try_add_async_system<P, S>(&mut self, system: S) -> EcsResult<SystemConfig<'_, K>>
     where
         P: SystemParams + 'static,
         S: AsyncSystem<P>,
     {
+        let world = self.world();
         let system_id = self.next_id;
         self.next_id += 1;
 
-        let contained = Arc::new(system.pinned(system_id, &self.world));
-        contained.init(&self.world);
+        let contained = Arc::new(system.pinned(system_id, &world));
+        self.insert_system(&world, contained)
+    }
 
-        dbg!(contained.descriptor());
+    /// Adds an async system whose future keeps running across runs of this schedule instead of
+    /// being awaited to completion, see [`BackgroundSystem`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system borrows the same data in conflicting ways.
+    pub fn add_background_system<P, S, Fut>(&mut self, system: S) -> SystemConfig<'_, K>
+    where
+        P: FetchParams + 'static,
+        S: Fn(Background<P>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Fallible,
+    {
+        let world = self.world();
+        let system_id = self.next_id;
+        self.next_id += 1;
+
+        let contained = Arc::new(BackgroundSystem::new(system_id, &world, system));
+        match self.insert_system(&world, contained) {
+            Ok(config) => config,
+            Err(err) => panic!("Cannot add system: {err}"),
+        }
+    }
+
+    /// Validates the access of a system, initialises it and adds it to the schedule.
+    fn insert_system(
+        &mut self,
+        world: &Arc<World>,
+        system: Arc<dyn System>,
+    ) -> EcsResult<SystemConfig<'_, K>> {
+        let descriptor = system.descriptor();
+        validate_access(&*system, &descriptor)?;
+
+        system.init(world);
+        self.systems.insert(
+            descriptor.id,
+            ScheduledSystem::new(system, descriptor.is_exclusive()),
+        );
+
+        Ok(self.configure(SystemId(descriptor.id)))
+    }
+
+    /// Adds multiple systems at once, see [`IntoSystems`].
+    ///
+    /// ```ignore
+    /// schedule.add_systems((detection, execution, reward).chain());
+    /// ```
+    pub fn add_systems<M>(&mut self, systems: impl IntoSystems<M>) -> Vec<SystemId> {
+        systems.add_to(self)
+    }
+
+    /// Removes a system from the schedule, returning whether it was part of it.
+    ///
+    /// The destroy hooks of the system and its run conditions are called, so that for example its
+    /// event readers stop being counted. Ordering constraints that refer to the removed system are ignored from now on.
+    pub fn remove_system(&mut self, id: SystemId) -> bool {
+        let Some(scheduled) = self.systems.remove(&id.0) else {
+            return false;
+        };
+
+        let world = self.world();
+        scheduled.system.destroy(&world);
+        for condition in scheduled.conditions {
+            condition.destroy(&world);
+        }
+
+        self.plan = None;
+        true
+    }
+
+    /// Replaces the function of a system, keeping its ID, sets, ordering constraints and run conditions.
+    ///
+    /// The destroy hooks of the old system are called before the new one is initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system is not part of this schedule or if the new system borrows the same data in conflicting ways.
+    pub fn replace_system<P, R, S>(&mut self, id: SystemId, system: S) -> SystemConfig<'_, K>
+    where
+        P: SystemParams + 'static,
+        R: SystemReturnable + 'static,
+        S: ParameterizedSystem<P, R> + 'static,
+        FnContainer<P, R, S>: System,
+    {
+        assert!(
+            self.systems.contains_key(&id.0),
+            "Cannot replace system, it is not part of this schedule."
+        );
+
+        let world = self.world();
+        let system: Arc<dyn System> = Arc::new(system.into_container(id.0, &world));
+        let descriptor = system.descriptor();
+        if let Err(err) = validate_access(&*system, &descriptor) {
+            panic!("Cannot replace system: {err}");
+        }
+
+        let scheduled = self.systems.get_mut(&id.0).unwrap();
+        scheduled.system.destroy(&world);
+        system.init(&world);
+
+        scheduled.system = system;
+        scheduled.exclusive = descriptor.is_exclusive();
+        scheduled.disabled = false;
+
+        self.configure(id)
+    }
+
+    /// Enables or disables a system. Disabled systems stay in the schedule but are skipped when it runs.
+    ///
+    /// This also re-enables systems that were disabled by [`ErrorHandler::Disable`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system is not part of this schedule.
+    pub fn set_enabled(&mut self, id: SystemId, enabled: bool) {
+        let scheduled = self
+            .systems
+            .get_mut(&id.0)
+            .expect("Cannot enable or disable system, it is not part of this schedule.");
+
+        scheduled.disabled = !enabled;
+    }
+
+    /// Returns whether a system is enabled, see [`set_enabled`](Self::set_enabled).
+    pub fn is_enabled(&self, id: SystemId) -> bool {
+        self.systems
+            .get(&id.0)
+            .is_some_and(|scheduled| !scheduled.disabled)
+    }
+
+    /// Changes the configuration of a system that has already been added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system is not part of this schedule.
+    pub fn configure(&mut self, id: SystemId) -> SystemConfig<'_, K> {
+        assert!(
+            self.systems.contains_key(&id.0),
+            "Cannot configure system, it is not part of this schedule."
+        );
+
+        self.plan = None;
+        SystemConfig { id, schedule: self }
+    }
+
+    /// Sets what happens when one of the systems in this schedule returns an error. Errors are logged by default.
+    pub fn set_error_handler(&mut self, handler: ErrorHandler) {
+        self.error_handler = handler;
+    }
+
+    /// Passes the error returned by a system to the error handler.
+    fn handle_error(&mut self, id: SystemId, err: SystemError) {
+        let scheduled = self.systems.get_mut(&id.0).unwrap();
+        let ctx = SystemErrorContext {
+            id,
+            name: scheduled.system.name(),
+        };
+
+        match &self.error_handler {
+            ErrorHandler::Log => eprintln!("System {} failed: {err}", ctx.name),
+            ErrorHandler::Panic => panic!("System {} failed: {err}", ctx.name),
+            ErrorHandler::Disable => {
+                eprintln!("System {} failed and has been disabled: {err}", ctx.name);
+                scheduled.disabled = true;
+            }
+            ErrorHandler::Custom(handler) => handler(ctx, err),
+        }
+    }
+
+    /// Configures the set with the given name, creating it if it does not exist yet.
+    pub fn configure_set(&mut self, set: &'static str) -> SetConfig<'_, K> {
+        self.plan = None;
+        self.sets.entry(set).or_default();
+
+        SetConfig {
+            set,
+            schedule: self,
+        }
+    }
+
+    /// Returns the names of all sets in this schedule, whether they have been configured or only have systems in them.
+    pub fn sets(&self) -> Vec<&'static str> {
+        let mut sets: Vec<&'static str> = self
+            .sets
+            .iter()
+            .flat_map(|(set, config)| std::iter::once(*set).chain(config.parents.iter().copied()))
+            .chain(self.systems.values().flat_map(|s| s.sets.iter().copied()))
+            .collect();
+
+        sets.sort_unstable();
+        sets.dedup();
+        sets
+    }
+
+    /// Returns the sets that `set` is directly nested in.
+    pub fn parent_sets(&self, set: &'static str) -> &[&'static str] {
+        self.sets.get(set).map_or(&[], |config| &config.parents)
+    }
+
+    /// Returns all systems in the given set, including those in nested sets.
+    pub fn systems_in_set(&self, set: &'static str) -> Vec<SystemId> {
+        let mut systems: Vec<SystemId> = self
+            .systems
+            .keys()
+            .map(|id| SystemId(*id))
+            .filter(|id| self.system_sets(*id).contains(&set))
+            .collect();
+
+        systems.sort_unstable();
+        systems
+    }
+
+    /// Returns all sets the system is part of, including the ones it is only indirectly part of through nesting.
+    fn system_sets(&self, id: SystemId) -> SmallVec<[&'static str; 4]> {
+        let mut sets: SmallVec<[&'static str; 4]> = SmallVec::new();
+        let mut pending: SmallVec<[&'static str; 4]> =
+            self.systems[&id.0].sets.iter().copied().collect();
+
+        while let Some(set) = pending.pop() {
+            if sets.contains(&set) {
+                continue;
+            }
 
-        self.systems.insert(system_id, contained);
-        SystemId(system_id)
+            sets.push(set);
+            pending.extend_from_slice(self.parent_sets(set));
+        }
+
+        sets
     }
 
+    /// Creates and initialises a run condition.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the condition requires mutable access.
+    fn init_condition<P, F>(&mut self, condition: F) -> Box<dyn Condition>
+    where
+        P: SystemParams + 'static,
+        F: ParameterizedSystem<P, bool> + 'static,
+        FnContainer<P, bool, F>: Condition,
+    {
+        let world = self.world();
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let condition = condition.into_container(id, &world);
+        assert!(
+            condition.descriptor().is_read_only(),
+            "Run condition {} must not require mutable access.",
+            condition.name()
+        );
+
+        condition.init(&world);
+        Box::new(condition)
+    }
+
+    /// Evaluates whether the system should run, based on its own run conditions and the sets it is part of.
+    ///
+    /// Set conditions are only evaluated once per run, the results are stored in `evaluated`.
+    fn should_run(
+        &self,
+        world: &Arc<World>,
+        id: SystemId,
+        evaluated: &mut HashMap<&'static str, bool>,
+    ) -> bool {
+        let sets_allow = self.system_sets(id).into_iter().all(|set| {
+            *evaluated.entry(set).or_insert_with(|| {
+                self.sets.get(set).is_none_or(|config| {
+                    !config.disabled && config.conditions.iter().all(|c| c.evaluate(world))
+                })
+            })
+        });
+
+        let scheduled = &self.systems[&id.0];
+        sets_allow && !scheduled.disabled && scheduled.conditions.iter().all(|c| c.evaluate(world))
+    }
+
+    /// Returns the batches the systems are currently run in, see [`OptimizedGraph`].
+    #[cfg(test)]
+    pub(crate) fn batches(&self) -> Option<&[Vec<SystemId>]> {
+        self.plan.as_ref().map(|plan| plan.batches.as_slice())
+    }
+
+    /// Validates the ordering constraints and builds the execution plan.
+    ///
+    /// The plan is cached until the systems in the schedule change. This is done automatically
+    /// when the schedule is run, but can be called beforehand to handle errors gracefully.
+    pub fn build(&mut self) -> EcsResult<()> {
+        if self.plan.is_none() {
+            self.plan = Some(self.build_plan()?);
+        }
+
+        Ok(())
+    }
+
+    /// Resolves an ordering target to the systems it refers to.
+    ///
+    /// Systems that are not part of this schedule are ignored.
+    fn resolve(&self, target: SystemTarget) -> SmallVec<[SystemId; 1]> {
+        match target {
+            SystemTarget::Id(id) if self.systems.contains_key(&id.0) => smallvec![id],
+            SystemTarget::Id(_) => SmallVec::new(),
+            SystemTarget::Set(set) => self.systems_in_set(set).into_iter().collect(),
+        }
+    }
+
+    /// Builds the execution plan from the current set of systems.
+    fn build_plan(&self) -> EcsResult<OptimizedGraph> {
+        // Sort to make the plan independent of the hash map order.
+        let mut ids: Vec<usize> = self.systems.keys().copied().collect();
+        ids.sort_unstable();
+
+        let descriptors: Vec<SystemDescriptor> = ids
+            .iter()
+            .map(|id| self.systems[id].system.descriptor())
+            .collect();
+
+        let mut graph = ScheduleGraph::from_descriptors(&descriptors);
+        for id in ids {
+            let system = &self.systems[&id];
+            let sets: SmallVec<[&SystemSet; 4]> = self
+                .system_sets(SystemId(id))
+                .into_iter()
+                .filter_map(|set| self.sets.get(set))
+                .collect();
+
+            // Ordering constraints of a set apply to every system in it.
+            let before = system
+                .before
+                .iter()
+                .chain(sets.iter().flat_map(|s| &s.before));
+            for target in before {
+                for other in self.resolve(*target) {
+                    graph.add_dependency((SystemId(id), other));
+                }
+            }
+
+            let after = system
+                .after
+                .iter()
+                .chain(sets.iter().flat_map(|s| &s.after));
+            for target in after {
+                for other in self.resolve(*target) {
+                    graph.add_dependency((other, SystemId(id)));
+                }
+            }
+        }
+
+        graph.optimize().map_err(|cycle| {
+            EcsError::ScheduleCycle(
+                cycle
+                    .iter()
+                    .map(|id| self.systems[&id.0].system.name())
+                    .collect(),
+            )
+        })
+    }
+
+    /// Builds the execution plan if necessary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the plan cannot be built.
+    fn plan(&mut self) -> &OptimizedGraph {
+        if let Err(err) = self.build() {
+            panic!("Cannot run schedule: {err}");
+        }
+
+        self.plan.as_ref().unwrap()
+    }
+}
+
+impl Schedule<SingleThreadedExecutor> {
+    /// Runs all systems once and then applies the changes they scheduled.
+    ///
+    /// Systems run one after another on the calling task, in the order they were added unless
+    /// their ordering constraints require otherwise. Async systems are awaited in place before the next system starts.
+    /// Nothing is spawned onto the runtime, so this also works on a current-thread runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ordering constraints contain a cycle, see [`build`](Self::build).
     pub async fn run(&mut self) {
-        self.world.scheduler.pre_tick(&self.world);
+        let world = self.world();
+        let _running = world.scheduler.enter_schedule();
+        world.scheduler.pre_tick(&world);
+
+        let order = self.plan().order.clone();
+        let mut evaluated = HashMap::new();
+        let mut failures = Vec::new();
+        for id in order {
+            if !self.should_run(&world, id, &mut evaluated) {
+                continue;
+            }
+
+            let scheduled = &self.systems[&id.0];
+            if scheduled.exclusive {
+                // Exclusive systems observe all changes made by the systems before them.
+                world.scheduler.post_tick(&world);
+            }
+
+            if let Err(err) = scheduled.system.call(&world).await {
+                failures.push((id, err));
+            }
+        }
+
+        world.scheduler.post_tick(&world);
+        for (id, err) in failures {
+            self.handle_error(id, err);
+        }
+    }
+}
+
+impl Schedule<MultiThreadedExecutor> {
+    /// Runs all systems once and then applies the changes they scheduled.
+    ///
+    /// Systems that do not conflict with each other run concurrently.
+    /// Systems that do, for example because they both mutably query the same component, run in separate batches.
+    /// Ordering constraints are respected by running systems in a later batch than the ones they run after.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ordering constraints contain a cycle, see [`build`](Self::build).
+    /// If a system panics, the panic is resumed once the other systems in its batch have completed.
+    pub async fn run(&mut self) {
+        let world = self.world();
+        let _running = world.scheduler.enter_schedule();
+        world.scheduler.pre_tick(&world);
 
         // Run systems. These cannot be transferred between threads while they're running due to
         // the query lock guards. For sync code this is not a problem, but tokio might move async tasks between threads
         // at await points. Therefore the systems need to run inside a LocalSet but this would only run systems
         // on the main thread. Maybe Rayon is a good idea?
 
-        let mut futures = FuturesUnordered::new();
-        for (id, system) in &self.systems {
-            let world = Arc::clone(&self.world);
-            let system = Arc::clone(system);
+        self.plan();
+        let plan = self.plan.as_ref().unwrap();
+        let mut evaluated = HashMap::new();
+        let mut failures = Vec::new();
+        for batch in &plan.batches {
+            // Evaluate the conditions before starting the batch, so that they never run concurrently with
+            // systems that might mutate the data they read.
+            let runnable: SmallVec<[SystemId; 8]> = batch
+                .iter()
+                .copied()
+                .filter(|id| self.should_run(&world, *id, &mut evaluated))
+                .collect();
+
+            // Exclusive systems conflict with every other system and therefore always run in a batch by themselves.
+            if let [id] = runnable[..] {
+                let scheduled = &self.systems[&id.0];
+                if scheduled.exclusive {
+                    world.scheduler.post_tick(&world);
+                    if let Err(err) = scheduled.system.call(&world).await {
+                        failures.push((id, err));
+                    }
+                    continue;
+                }
+            }
+
+            let mut futures = FuturesUnordered::new();
+            for id in runnable {
+                let world = Arc::clone(&world);
+                let system = Arc::clone(&self.systems[&id.0].system);
+
+                let task = tokio::spawn(async move { system.call(&world).await });
+                futures.push(async move { (id, task.await) });
+            }
 
-            futures.push(tokio::spawn(async move {
-                system.call(&world).await;
-            }));
+            // Run the entire batch to completion before starting the next one.
+            let mut panic = None;
+            while let Some((id, joined)) = futures.next().await {
+                match joined {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => failures.push((id, err)),
+                    Err(err) if err.is_panic() => {
+                        panic.get_or_insert(err.into_panic());
+                    }
+                    Err(err) => failures.push((id, Box::new(err))),
+                }
+            }
+
+            // Propagate panics like the single-threaded executor does, once no other system is running anymore.
+            if let Some(payload) = panic {
+                std::panic::resume_unwind(payload);
+            }
         }
 
-        // let mut local_set = LocalSet::new();
+        world.scheduler.post_tick(&world);
+        for (id, err) in failures {
+            self.handle_error(id, err);
+        }
+    }
+}
 
-        // for sys_index in 0..self.systems.len() {
-        //     let world = Arc::clone(&self.world);
+/// Identifies one of the schedules owned by the [`World`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ScheduleLabel {
+    /// Runs once, at the start of the first [`World::tick`].
+    Startup,
+    /// Runs at the start of every tick.
+    PreUpdate,
+    /// Runs every tick, after [`PreUpdate`](Self::PreUpdate). This is where most systems belong.
+    Update,
+    /// Runs at the end of every tick, after [`Update`](Self::Update).
+    PostUpdate,
+    /// Runs once, when the world is shut down with [`World::exit`].
+    Shutdown,
+}
+
+impl ScheduleLabel {
+    /// The schedules run by every [`World::tick`], in order.
+    pub const TICK: [ScheduleLabel; 3] = [
+        ScheduleLabel::PreUpdate,
+        ScheduleLabel::Update,
+        ScheduleLabel::PostUpdate,
+    ];
+}
+
+/// The schedules owned by the world.
+#[derive(Default)]
+pub(crate) struct Schedules {
+    /// Schedules that are currently running are temporarily taken out of this map.
+    map: Mutex<HashMap<ScheduleLabel, Schedule<MultiThreadedExecutor>>>,
+    /// Labels of the schedules that are currently running.
+    running: Mutex<HashSet<ScheduleLabel>>,
+    /// Whether the startup schedule has already run.
+    started: AtomicBool,
+    pub(crate) world: Weak<World>,
+}
+
+impl Schedules {
+    /// Gives mutable access to the schedule with the given label, creating it if it does not exist yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule is currently running.
+    pub(crate) fn scope<T>(
+        &self,
+        label: ScheduleLabel,
+        f: impl FnOnce(&mut Schedule<MultiThreadedExecutor>) -> T,
+    ) -> T {
+        let mut map = self.map.lock();
+        assert!(
+            !self.running.lock().contains(&label),
+            "Cannot modify the {label:?} schedule while it is running."
+        );
 
-        //     let sys = self.systems[sys_index].clone();
-        //     futures.push(tokio::spawn(async move {
-        //         sys.call(&world).await;
-        //     }));
-        // }
+        let schedule = map
+            .entry(label)
+            .or_insert_with(|| Schedule::from_weak(Weak::clone(&self.world)));
 
-        // Run all futures to completion
-        while let Some(_) = futures.next().await {}
+        f(schedule)
+    }
+
+    /// Runs the schedule with the given label, if it exists.
+    pub(crate) async fn run(&self, label: ScheduleLabel) {
+        let schedule = {
+            let mut map = self.map.lock();
+            let Some(schedule) = map.remove(&label) else {
+                return;
+            };
+
+            self.running.lock().insert(label);
+            schedule
+        };
+
+        let mut guard = RunningSchedule {
+            schedules: self,
+            label,
+            schedule: Some(schedule),
+        };
+
+        if let Some(schedule) = &mut guard.schedule {
+            schedule.run().await;
+        }
+    }
 
-        self.world.scheduler.post_tick(&self.world);
+    /// Marks the startup schedule as run, returning whether it had not run before.
+    pub(crate) fn start(&self) -> bool {
+        !self.started.swap(true, Ordering::SeqCst)
+    }
+
+    /// Destroys and drops all schedules.
+    pub(crate) fn clear(&self, world: &Arc<World>) {
+        let schedules = std::mem::take(&mut *self.map.lock());
+        for (_, mut schedule) in schedules {
+            schedule.clear(world);
+        }
     }
 }
 
-impl Schedule<SingleThreadedExecutor> {
-    fn schedule_graph(&self) -> ScheduleGraph {
-        todo!()
+/// Puts a schedule that has been taken out to run back into the map, even if one of its systems panics.
+struct RunningSchedule<'a> {
+    schedules: &'a Schedules,
+    label: ScheduleLabel,
+    schedule: Option<Schedule<MultiThreadedExecutor>>,
+}
+
+impl Drop for RunningSchedule<'_> {
+    fn drop(&mut self) {
+        if let Some(schedule) = self.schedule.take() {
+            self.schedules.map.lock().insert(self.label, schedule);
+        }
+        self.schedules.running.lock().remove(&self.label);
     }
 }
 
-impl Schedule<MultiThreadedExecutor> {
-    fn schedule_graph(&self) -> ScheduleGraph {
-        todo!()
+/// Systems that do not belong to a schedule and only run when requested, see [`World::run_system`].
+#[derive(Default)]
+pub(crate) struct OneShotSystems {
+    next_id: AtomicUsize,
+    map: Mutex<HashMap<usize, Arc<dyn System>, BuildNoHashHasher<usize>>>,
+    /// IDs of the systems that are currently running.
+    running: Mutex<HashSet<usize>>,
+}
+
+impl OneShotSystems {
+    /// Returns the ID to give to the next registered system.
+    pub(crate) fn next_id(&self) -> usize {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Validates the access of a system, initialises it and stores it until it is unregistered.
+    pub(crate) fn register(
+        &self,
+        world: &Arc<World>,
+        system: Arc<dyn System>,
+    ) -> EcsResult<OneShotSystemId> {
+        let descriptor = system.descriptor();
+        validate_access(&*system, &descriptor)?;
+
+        system.init(world);
+        self.map.lock().insert(descriptor.id, system);
+
+        Ok(OneShotSystemId(descriptor.id))
+    }
+
+    /// Runs the system with the given ID and applies the changes it scheduled, see [`World::run_system`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system is already running.
+    pub(crate) async fn run(&self, world: &Arc<World>, id: OneShotSystemId) -> SystemResult {
+        let system = self
+            .map
+            .lock()
+            .get(&id.0)
+            .cloned()
+            .ok_or(EcsError::NotFound)?;
+
+        // The state of a system can only be used by one invocation at a time.
+        assert!(
+            self.running.lock().insert(id.0),
+            "Cannot run system {}, it is already running.",
+            system.name()
+        );
+        let _running = RunningOneShot {
+            running: &self.running,
+            id: id.0,
+        };
+
+        // The systems of a running schedule may still hold their parameters, so applying changes now
+        // would fail. The schedule applies them instead once its systems have completed.
+        let flush = !world.scheduler.is_running_schedule();
+        if flush {
+            world.scheduler.pre_tick(world);
+        }
+
+        let result = system.call(world).await;
+        if flush {
+            world.scheduler.post_tick(world);
+        }
+
+        result
     }
+
+    /// Removes a system, returning whether it was registered.
+    pub(crate) fn unregister(&self, world: &Arc<World>, id: OneShotSystemId) -> bool {
+        let removed = self.map.lock().remove(&id.0);
+        if let Some(system) = &removed {
+            system.destroy(world);
+        }
+
+        removed.is_some()
+    }
+
+    /// Destroys and drops all systems.
+    pub(crate) fn clear(&self, world: &Arc<World>) {
+        let systems = std::mem::take(&mut *self.map.lock());
+        for (_, system) in systems {
+            system.destroy(world);
+        }
+    }
+}
+
+/// Marks a one-shot system as no longer running when dropped, even if the system panics.
+struct RunningOneShot<'a> {
+    running: &'a Mutex<HashSet<usize>>,
+    id: usize,
+}
+
+impl Drop for RunningOneShot<'_> {
+    fn drop(&mut self) {
+        self.running.lock().remove(&self.id);
+    }
+}
+
+/// Rejects systems that borrow the same data in conflicting ways, see [`SystemDescriptor::access_conflicts`],
+/// and systems that expect piped input outside of a pipe, see [`SystemDescriptor::has_unpiped_input`].
+fn validate_access(system: &dyn System, descriptor: &SystemDescriptor) -> EcsResult<()> {
+    if descriptor.has_unpiped_input() {
+        return Err(EcsError::UnpipedInput(system.name()));
+    }
+
+    let conflicts = descriptor.access_conflicts();
+    if !conflicts.is_empty() {
+        return Err(EcsError::ConflictingAccess {
+            system: system.name(),
+            types: conflicts,
+        });
+    }
+
+    Ok(())
 }
 
+/// Marks a schedule as no longer running when dropped, see [`Scheduler::enter_schedule`].
+pub(crate) struct ScheduleRunGuard<'a> {
+    scheduler: &'a Scheduler,
+}
+
+impl Drop for ScheduleRunGuard<'_> {
+    fn drop(&mut self) {
+        self.scheduler
+            .running_schedules
+            .fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A deferred change to the world that is applied at the end of a tick.
+pub(crate) type Command = Box<dyn FnOnce(&Arc<World>) + Send>;
+
 #[derive(Default)]
 pub struct Scheduler {
+    /// Keeps track of commands, such as component insertions, to apply at the end of a tick.
+    command_queue: Mutex<Vec<Command>>,
     /// Keeps track of entities that need to be despawned at the end of a tick.
     despawn_queue: DashSet<EntityId>,
     /// Keeps track of components to remove from entities at the end of a tick.
     remove_queue: DashMap<TypeId, HashSet<EntityId>>,
+    /// The number of schedules that are currently running.
+    running_schedules: AtomicUsize,
 }
 
 impl Scheduler {
@@ -221,6 +1666,24 @@ impl Scheduler {
         self.despawn_queue.insert(entity);
     }
 
+    /// Schedules a command that is applied to the world at the end of the tick.
+    pub(crate) fn schedule_command<F>(&self, command: F)
+    where
+        F: FnOnce(&Arc<World>) + Send + 'static,
+    {
+        self.command_queue.lock().push(Box::new(command));
+    }
+
+    pub fn schedule_insert<B: SpawnBundle + Send + 'static>(&self, entity: EntityId, bundle: B) {
+        self.schedule_command(move |world| {
+            if world.entities.contains(entity) {
+                bundle
+                    .insert_into(&world.components, entity)
+                    .expect("Cannot insert components, storage is locked.");
+            }
+        });
+    }
+
     pub fn schedule_remove_component(&self, entity: EntityId, type_id: TypeId) {
         let mut entry = self
             .remove_queue
@@ -230,32 +1693,98 @@ impl Scheduler {
         entry.value_mut().insert(entity);
     }
 
+    /// Schedules the removal of multiple components from an entity as a single operation,
+    /// see [`Components::remove_many`](crate::Components::remove_many).
+    pub fn schedule_remove_components<I>(&self, entity: EntityId, type_ids: I)
+    where
+        I: IntoIterator<Item = TypeId>,
+    {
+        let type_ids: SmallVec<[TypeId; 3]> = type_ids.into_iter().collect();
+        self.schedule_command(move |world| {
+            if world.entities.contains(entity) {
+                world
+                    .components
+                    .remove_many(entity, &type_ids)
+                    .expect("Cannot remove components, storage is locked.");
+            }
+        });
+    }
+
+    /// Marks a schedule as running until the returned guard is dropped.
+    pub(crate) fn enter_schedule(&self) -> ScheduleRunGuard<'_> {
+        self.running_schedules.fetch_add(1, Ordering::SeqCst);
+        ScheduleRunGuard { scheduler: self }
+    }
+
+    /// Returns whether any schedule is currently running.
+    pub(crate) fn is_running_schedule(&self) -> bool {
+        self.running_schedules.load(Ordering::SeqCst) > 0
+    }
+
+    /// Discards all pending changes.
+    pub(crate) fn clear(&self) {
+        self.command_queue.lock().clear();
+        self.remove_queue.clear();
+        self.despawn_queue.clear();
+    }
+
     pub fn pre_tick(&self, _world: &Arc<World>) {}
 
     pub fn post_tick(&self, world: &Arc<World>) {
-        self.tick_removal(world);
-        self.tick_despawn(world);
+        // Applying changes can schedule new ones, for example through component hooks.
+        // Keep flushing until everything has been applied so that all changes land in the same tick.
+        loop {
+            self.tick_commands(world);
+            self.tick_removal(world);
+            self.tick_despawn(world);
+
+            if self.is_flushed() {
+                break;
+            }
+        }
+    }
+
+    /// Returns whether there are no pending changes left.
+    fn is_flushed(&self) -> bool {
+        self.command_queue.lock().is_empty()
+            && self.remove_queue.is_empty()
+            && self.despawn_queue.is_empty()
+    }
+
+    fn tick_commands(&self, world: &Arc<World>) {
+        let queue = std::mem::take(&mut *self.command_queue.lock());
+        for command in queue {
+            command(world);
+        }
     }
 
     fn tick_removal(&self, world: &Arc<World>) {
-        self.remove_queue.retain(|type_id, entities| {
-            if let Some(store_kv) = world.components.map.get(type_id) {
-                for entity in entities.iter() {
-                    store_kv.value().remove(*entity);
-                }
-            }
+        // Drain the queue before removing anything, component hooks might schedule new removals.
+        let type_ids: Vec<TypeId> = self.remove_queue.iter().map(|kv| *kv.key()).collect();
+        for type_id in type_ids {
+            let Some((_, entities)) = self.remove_queue.remove(&type_id) else {
+                continue;
+            };
 
-            false
-        });
+            for entity in entities {
+                world
+                    .components
+                    .remove_by_id(type_id, entity)
+                    .expect("Cannot remove component, storage is locked.");
+            }
+        }
     }
 
     fn tick_despawn(&self, world: &Arc<World>) {
-        world
-            .entities
-            .free_many(self.despawn_queue.iter().map(|kv| *kv.key()));
-        self.despawn_queue.retain(|entity| {
+        // Drain the queue before despawning anything, component hooks might schedule new despawns.
+        let entities: Vec<EntityId> = self.despawn_queue.iter().map(|kv| *kv.key()).collect();
+        for entity in &entities {
+            self.despawn_queue.remove(entity);
+            // Remove relations pointing to this entity so that they never dangle.
+            world.relations.clear_target(world, *entity);
             world.components.despawn(*entity);
-            false
-        });
+        }
+
+        world.entities.free_many(entities.into_iter());
     }
 }
@@ -1,44 +1,38 @@
-use futures::stream::FuturesUnordered;
-use futures::StreamExt;
-use parking_lot::RwLock;
-use std::{
-    any::TypeId,
-    future::Future,
-    marker::PhantomData,
-    pin::Pin,
-    sync::Arc
-};
+use std::{future::Future, marker::PhantomData, pin::Pin, sync::Arc};
 
 use crate::{
     scheduler::{SystemDescriptor, SystemParamDescriptor},
-    sealed, World,
+    sealed, SystemError, SystemResult, World,
 };
 
 pub unsafe trait System: Send + Sync {
     fn descriptor(&self) -> SystemDescriptor;
 
+    /// The name of the system, used in diagnostics.
+    fn name(&self) -> &'static str;
+
     /// # Safety
     ///
     /// Before running a system you must ensure that the Rust reference aliasing guarantees are upheld.
     /// Any systems requiring mutable access to a component must have unique access.
-    fn call(&self, world: &Arc<World>) -> Pin<Box<dyn Future<Output = ()> + Send + Sync>>;
+    fn call(&self, world: &Arc<World>) -> PinnedFut;
 
-    /// This function takes a self parameter to make [`System`] object-safe.
+    /// Whether the system is an async function.
     ///
-    /// # Safety
-    ///
-    /// This function *must* only return `true` for systems that return the type `Pin<Box<dyn Future<Output = ()> + Send + Sync>>`.
+    /// This function takes a self parameter to make [`System`] object-safe.
     fn is_async(&self) -> bool;
 
     /// Runs any preparations before a system's first run.
     /// This is for example used to register all active event readers.
     fn init(&self, _world: &Arc<World>) {}
+    /// Undoes the preparations made by [`init`](Self::init) when the system is removed.
     fn destroy(&self, _world: &Arc<World>) {}
 }
 
 /// Wrapper around a system function pointer to be able to store the function's params.
 pub struct FnContainer<P: SystemParams, R: SystemReturnable, F: ParameterizedSystem<P, R>> {
     pub id: usize,
+    pub name: &'static str,
     pub system: F,
     pub state: P::ArcState,
     pub _marker: PhantomData<(P, R)>,
@@ -48,6 +42,12 @@ pub trait SystemParams {
     type ArcState: Send + Sync;
 
     fn state(world: &Arc<World>) -> Self::ArcState;
+    /// Describes the access of every parameter.
+    fn descriptors() -> Vec<SystemParamDescriptor>;
+    /// Initializes every parameter for first-time use, see [`SystemParam::init`].
+    fn init(world: &Arc<World>, state: &Self::ArcState);
+    /// Deinitializes every parameter, see [`SystemParam::destroy`].
+    fn destroy(world: &Arc<World>, state: &Self::ArcState);
 }
 
 impl<P: SystemParam> SystemParams for P {
@@ -56,6 +56,18 @@ impl<P: SystemParam> SystemParams for P {
     fn state(world: &Arc<World>) -> Self::ArcState {
         P::state(world)
     }
+
+    fn descriptors() -> Vec<SystemParamDescriptor> {
+        vec![P::descriptor()]
+    }
+
+    fn init(world: &Arc<World>, state: &Self::ArcState) {
+        P::init(world, state);
+    }
+
+    fn destroy(world: &Arc<World>, state: &Self::ArcState) {
+        P::destroy(world, state);
+    }
 }
 
 impl<P1: SystemParam, P2: SystemParam> SystemParams for (P1, P2) {
@@ -64,6 +76,20 @@ impl<P1: SystemParam, P2: SystemParam> SystemParams for (P1, P2) {
     fn state(world: &Arc<World>) -> Self::ArcState {
         (P1::state(world), P2::state(world))
     }
+
+    fn descriptors() -> Vec<SystemParamDescriptor> {
+        vec![P1::descriptor(), P2::descriptor()]
+    }
+
+    fn init(world: &Arc<World>, state: &Self::ArcState) {
+        P1::init(world, &state.0);
+        P2::init(world, &state.1);
+    }
+
+    fn destroy(world: &Arc<World>, state: &Self::ArcState) {
+        P1::destroy(world, &state.0);
+        P2::destroy(world, &state.1);
+    }
 }
 
 impl<P1, P2, P3> SystemParams for (P1, P2, P3)
@@ -77,6 +103,22 @@ where
     fn state(world: &Arc<World>) -> Self::ArcState {
         (P1::state(world), P2::state(world), P3::state(world))
     }
+
+    fn descriptors() -> Vec<SystemParamDescriptor> {
+        vec![P1::descriptor(), P2::descriptor(), P3::descriptor()]
+    }
+
+    fn init(world: &Arc<World>, state: &Self::ArcState) {
+        P1::init(world, &state.0);
+        P2::init(world, &state.1);
+        P3::init(world, &state.2);
+    }
+
+    fn destroy(world: &Arc<World>, state: &Self::ArcState) {
+        P1::destroy(world, &state.0);
+        P2::destroy(world, &state.1);
+        P3::destroy(world, &state.2);
+    }
 }
 
 unsafe impl<P, R, F: ParameterizedSystem<P, R>> System for FnContainer<P, R, F>
@@ -91,29 +133,12 @@ where
         }
     }
 
-    fn call(&self, world: &Arc<World>) -> Pin<Box<dyn Future<Output = ()> + Send + Sync>> {
-        let returned = self.system.call(world, &self.state);
-        if self.is_async() {
-            debug_assert_eq!(
-                TypeId::of::<R>(),
-                TypeId::of::<Pin<Box<dyn Future<Output = ()> + Send + Sync>>>()
-            );
-
-            // SAFETY: `System::is_async` will only return `true` when `R == Pin<Box<dyn Future<Output = ()> + Send + Sync>>`.
-            // It is therefore safe to transmute as both types are equal.
-            let cast = unsafe {
-                std::mem::transmute_copy::<R, Pin<Box<dyn Future<Output = ()> + Send + Sync>>>(
-                    &returned,
-                )
-            };
-            // Prevent dropping the Box, preventing a use-after-free.
-            std::mem::forget(returned);
-
-            cast
-        } else {
-            // Return empty future.
-            Box::pin(async {})
-        }
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn call(&self, world: &Arc<World>) -> PinnedFut {
+        self.system.call(world, &self.state).into_future()
     }
 
     #[inline]
@@ -124,6 +149,10 @@ where
     fn init(&self, world: &Arc<World>) {
         P::init(world, &self.state);
     }
+
+    fn destroy(&self, world: &Arc<World>) {
+        P::destroy(world, &self.state);
+    }
 }
 
 unsafe impl<P1, P2, R, F: ParameterizedSystem<(P1, P2), R>> System for FnContainer<(P1, P2), R, F>
@@ -139,29 +168,12 @@ where
         }
     }
 
-    fn call(&self, world: &Arc<World>) -> Pin<Box<dyn Future<Output = ()> + Send + Sync>> {
-        let returned = self.system.call(world, &self.state);
-        if self.is_async() {
-            debug_assert_eq!(
-                TypeId::of::<R>(),
-                TypeId::of::<Pin<Box<dyn Future<Output = ()> + Send + Sync>>>()
-            );
-
-            // SAFETY: `System::is_async` will only return `true` when `R == Pin<Box<dyn Future<Output = ()> + Send + Sync>>`.
-            // It is therefore safe to transmute as both types are equal.
-            let cast = unsafe {
-                std::mem::transmute_copy::<R, Pin<Box<dyn Future<Output = ()> + Send + Sync>>>(
-                    &returned,
-                )
-            };
-            // Prevent dropping the Box, preventing a use-after-free.
-            std::mem::forget(returned);
-
-            cast
-        } else {
-            // Return empty future.
-            Box::pin(async {})
-        }
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn call(&self, world: &Arc<World>) -> PinnedFut {
+        self.system.call(world, &self.state).into_future()
     }
 
     #[inline]
@@ -173,6 +185,11 @@ where
         P1::init(world, &self.state.0);
         P2::init(world, &self.state.1);
     }
+
+    fn destroy(&self, world: &Arc<World>) {
+        P1::destroy(world, &self.state.0);
+        P2::destroy(world, &self.state.1);
+    }
 }
 
 unsafe impl<P1, P2, P3, R, F: ParameterizedSystem<(P1, P2, P3), R>> System
@@ -190,29 +207,12 @@ where
         }
     }
 
-    fn call(&self, world: &Arc<World>) -> Pin<Box<dyn Future<Output = ()> + Send + Sync>> {
-        let returned = self.system.call(world, &self.state);
-        if self.is_async() {
-            debug_assert_eq!(
-                TypeId::of::<R>(),
-                TypeId::of::<Pin<Box<dyn Future<Output = ()> + Send + Sync>>>()
-            );
-
-            // SAFETY: `System::is_async` will only return `true` when `R == Pin<Box<dyn Future<Output = ()> + Send + Sync>>`.
-            // It is therefore safe to transmute as both types are equal.
-            let cast = unsafe {
-                std::mem::transmute_copy::<R, Pin<Box<dyn Future<Output = ()> + Send + Sync>>>(
-                    &returned,
-                )
-            };
-            // Prevent dropping the Box, preventing a use-after-free.
-            std::mem::forget(returned);
-
-            cast
-        } else {
-            // Return empty future.
-            Box::pin(async {})
-        }
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn call(&self, world: &Arc<World>) -> PinnedFut {
+        self.system.call(world, &self.state).into_future()
     }
 
     #[inline]
@@ -225,6 +225,12 @@ where
         P2::init(world, &self.state.1);
         P3::init(world, &self.state.2);
     }
+
+    fn destroy(&self, world: &Arc<World>) {
+        P1::destroy(world, &self.state.0);
+        P2::destroy(world, &self.state.1);
+        P3::destroy(world, &self.state.2);
+    }
 }
 
 pub trait SystemParam: Send + Sync {
@@ -257,7 +263,7 @@ impl SystemParam for () {
     }
 }
 
-pub type PinnedFut = Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>>;
+pub type PinnedFut = Pin<Box<dyn Future<Output = SystemResult> + Send + Sync + 'static>>;
 
 /// Implemented by async systems to put them into storage containers.
 pub trait AsyncSystem<P>
@@ -271,65 +277,140 @@ where
 impl<P, F, Fut> AsyncSystem<P> for F
 where
     F: Fn(P) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = ()> + Send + Sync + 'static,
+    Fut: Future + Send + Sync + 'static,
+    Fut::Output: Fallible,
     P: SystemParam + 'static,
 {
     fn pinned(self, id: usize, world: &Arc<World>) -> impl System + Send + Sync + 'static {
-        let pinned = move |p| -> PinnedFut { Box::pin(self(p)) };
-
-        pinned.into_container(id, world)
+        let pinned = move |p| -> PinnedFut {
+            let fut = self(p);
+            Box::pin(async move { fut.await.into_result() })
+        };
+
+        let mut container = pinned.into_container(id, world);
+        container.name = std::any::type_name::<F>();
+        container
     }
 }
 
 impl<P1, P2, F, Fut> AsyncSystem<(P1, P2)> for F
 where
     F: Fn(P1, P2) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = ()> + Send + Sync + 'static,
+    Fut: Future + Send + Sync + 'static,
+    Fut::Output: Fallible,
     P1: SystemParam + 'static,
     P2: SystemParam + 'static,
 {
     fn pinned(self, id: usize, world: &Arc<World>) -> impl System + Send + Sync + 'static {
-        let pinned = move |p1, p2| -> PinnedFut { Box::pin(self(p1, p2)) };
-
-        pinned.into_container(id, world)
+        let pinned = move |p1, p2| -> PinnedFut {
+            let fut = self(p1, p2);
+            Box::pin(async move { fut.await.into_result() })
+        };
+
+        let mut container = pinned.into_container(id, world);
+        container.name = std::any::type_name::<F>();
+        container
     }
 }
 
 impl<P1, P2, P3, F, Fut> AsyncSystem<(P1, P2, P3)> for F
 where
     F: Fn(P1, P2, P3) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = ()> + Send + Sync + 'static,
+    Fut: Future + Send + Sync + 'static,
+    Fut::Output: Fallible,
     P1: SystemParam + 'static,
     P2: SystemParam + 'static,
     P3: SystemParam + 'static,
 {
     fn pinned(self, id: usize, world: &Arc<World>) -> impl System + Send + Sync + 'static {
-        let pinned = move |p1, p2, p3| -> PinnedFut { Box::pin(self(p1, p2, p3)) };
-
-        pinned.into_container(id, world)
+        let pinned = move |p1, p2, p3| -> PinnedFut {
+            let fut = self(p1, p2, p3);
+            Box::pin(async move { fut.await.into_result() })
+        };
+
+        let mut container = pinned.into_container(id, world);
+        container.name = std::any::type_name::<F>();
+        container
     }
 }
 
 /// Types that can be used as return values for a system.
 ///
-/// This is useful for async systems which return futures but is also implemented for unit in the case of sync systems.
-/// In the future this could possibly also be used for returning errors or something in that direction.
+/// This is implemented for the futures returned by async systems, for unit and results in the case of sync systems,
+/// and for booleans in the case of [`Condition`](crate::Condition)s.
 pub trait SystemReturnable: Send + Sync + 'static {
     const IS_ASYNC: bool;
+
+    /// Converts the returned value into a future that the executor can await.
+    fn into_future(self) -> PinnedFut;
 }
 
 impl SystemReturnable for () {
     const IS_ASYNC: bool = false;
+
+    fn into_future(self) -> PinnedFut {
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+impl<E: Into<SystemError> + Send + Sync + 'static> SystemReturnable for Result<(), E> {
+    const IS_ASYNC: bool = false;
+
+    fn into_future(self) -> PinnedFut {
+        Box::pin(std::future::ready(self.into_result()))
+    }
+}
+
+/// Returned by [`Condition`](crate::Condition)s.
+impl SystemReturnable for bool {
+    const IS_ASYNC: bool = false;
+
+    fn into_future(self) -> PinnedFut {
+        Box::pin(std::future::ready(Ok(())))
+    }
 }
 
-impl SystemReturnable for Pin<Box<dyn Future<Output = ()> + Send + Sync>> {
+impl SystemReturnable for PinnedFut {
     const IS_ASYNC: bool = true;
+
+    fn into_future(self) -> PinnedFut {
+        self
+    }
+}
+
+/// The outputs of systems that can fail, so that they can use `?`.
+///
+/// Errors are passed to the [`ErrorHandler`](crate::ErrorHandler) of the schedule the system runs in.
+pub trait Fallible: Send + Sync + 'static {
+    fn into_result(self) -> SystemResult;
+}
+
+impl Fallible for () {
+    fn into_result(self) -> SystemResult {
+        Ok(())
+    }
+}
+
+/// Implemented for any error convertible into a [`SystemError`], including [`SystemResult`] itself,
+/// so that a system can use `?` on several error types.
+impl<E: Into<SystemError> + Send + Sync + 'static> Fallible for Result<(), E> {
+    fn into_result(self) -> SystemResult {
+        self.map_err(Into::into)
+    }
 }
 
-pub trait ParameterizedSystem<P: SystemParams, R: SystemReturnable>: Send + Sync + Sized {
-    fn into_container(self, id: usize, world: &Arc<World>) -> FnContainer<P, R, Self> {
+/// A function that can run as a system.
+///
+/// The return value `R` only has to be [`SystemReturnable`] to be added to a schedule by itself,
+/// systems whose output is [piped](crate::PipeSystem::pipe) into another system can return anything.
+pub trait ParameterizedSystem<P: SystemParams, R>: Send + Sync + Sized {
+    fn into_container(self, id: usize, world: &Arc<World>) -> FnContainer<P, R, Self>
+    where
+        R: SystemReturnable,
+    {
         FnContainer {
             id,
+            name: std::any::type_name::<Self>(),
             system: self,
             state: P::state(world),
             _marker: PhantomData,
@@ -343,7 +424,6 @@ impl<F, R, P> ParameterizedSystem<P, R> for F
 where
     F: Fn(P) -> R + Send + Sync,
     P: SystemParam,
-    R: SystemReturnable,
 {
     fn call(&self, world: &Arc<World>, state: &Arc<P::State>) -> R {
         let p = P::fetch::<sealed::Sealer>(world, state);
@@ -356,7 +436,6 @@ where
     F: Fn(P1, P2) -> R + Send + Sync,
     P1: SystemParam,
     P2: SystemParam,
-    R: SystemReturnable,
 {
     fn call(&self, world: &Arc<World>, state: &<(P1, P2) as SystemParams>::ArcState) -> R {
         let p1 = P1::fetch::<sealed::Sealer>(world, &state.0);
@@ -371,7 +450,6 @@ where
     P1: SystemParam,
     P2: SystemParam,
     P3: SystemParam,
-    R: SystemReturnable,
 {
     fn call(&self, world: &Arc<World>, state: &<(P1, P2, P3) as SystemParams>::ArcState) -> R {
         let p1 = P1::fetch::<sealed::Sealer>(world, &state.0);
@@ -380,44 +458,3 @@ where
         self(p1, p2, p3)
     }
 }
-
-pub struct Systems {
-    storage: RwLock<Vec<Arc<dyn System + Send + Sync>>>,
-}
-
-impl Systems {
-    pub fn new() -> Systems {
-        Systems::default()
-    }
-
-    pub fn push(&self, world: &Arc<World>, system: Arc<dyn System + Send + Sync>) {
-        // Initialise system state.
-        system.init(world);
-        self.storage.write().push(system);
-    }
-
-    pub async fn call(&self, world: &Arc<World>) {
-        let mut futures = FuturesUnordered::new();
-
-        let lock = self.storage.read();
-        for sys_index in 0..self.storage.read().len() {
-            let world = Arc::clone(world);
-
-            let sys = lock[sys_index].clone();
-            futures.push(tokio::spawn(async move {
-                sys.call(&world).await;
-            }));
-        }
-
-        // Run all futures to completion
-        while let Some(_) = futures.next().await {}
-    }
-}
-
-impl Default for Systems {
-    fn default() -> Systems {
-        Systems {
-            storage: RwLock::new(Vec::new()),
-        }
-    }
-}
@@ -1,9 +1,16 @@
 use ecs_derive::Component;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crate::entity::Entity;
+use crate::entity::{Disabled, Entity, EntityId, EntityMut, EntityRef};
+use crate::scheduler::SystemParamDescriptor;
 use crate::{
-    Component, Event, EventReader, EventWriter, Query, Res, ResMut, Resource, State, Without, World,
+    every_n_ticks, in_state, on_event, resource_exists, Background, Children, DeferredWorld,
+    EcsError, EcsResult, ErrorHandler, Event, EventReader, EventWriter, ExclusiveWorld, In,
+    IntoSystems, ParamSet, Parent, PipeSystem, Query, Related, Relation, Res, ResMut, Resource,
+    ScheduleLabel, State, SystemParam, SystemResult, With, WithDisabled, Without, World,
 };
 
 // static GLOBAL: RwLock<Option<&'static Health>> = RwLock::new(None);
@@ -95,9 +102,26 @@ impl Resource for KillCounter {}
 #[derive(Debug, Component)]
 struct Immortal;
 
-#[derive(Debug, Component)]
+#[derive(Debug, Default, Component)]
 struct Health(f32);
 
+#[derive(Debug, Component)]
+#[component(requires(Position = Position::origin, Armor))]
+struct Player;
+
+#[derive(Debug, Default, Component)]
+#[component(requires(Health = Health(1.0)))]
+struct Armor(u32);
+
+#[derive(Debug, PartialEq, Component)]
+struct Position(f32, f32);
+
+impl Position {
+    fn origin() -> Self {
+        Position(0.0, 0.0)
+    }
+}
+
 #[derive(Clone)]
 struct Killed {
     entity: Entity,
@@ -125,8 +149,9 @@ async fn test() {
 
     schedule.add_system(interval_system);
     schedule.add_system(reader);
-    schedule.add_system(detection);
-    schedule.add_system(execution);
+    // Kills are detected and executed in the same tick.
+    let detection = schedule.add_system(detection).id();
+    schedule.add_system(execution).after(detection);
     schedule.add_system(state_system);
     schedule.add_async_system(async_system);
 
@@ -135,4 +160,1194 @@ async fn test() {
         schedule.run().await;
         interval.tick().await;
     }
+
+    world.shutdown().unwrap();
+}
+
+#[test]
+fn entity_get() {
+    let world = World::new();
+    let entity = world.spawn(Health(1.0));
+
+    assert_eq!(entity.get::<Health>().unwrap().0, 1.0);
+    entity.get_mut::<Health>().unwrap().0 = 2.0;
+    assert_eq!(entity.get::<Health>().unwrap().0, 2.0);
+    assert!(matches!(entity.get::<Immortal>(), Err(EcsError::NotFound)));
+
+    // Storage is locked while a reference is held.
+    let health = entity.get_mut::<Health>().unwrap();
+    assert!(matches!(
+        entity.get::<Health>(),
+        Err(EcsError::StorageLocked(_))
+    ));
+    drop(health);
+
+    let _health = entity.get::<Health>().unwrap();
+    assert!(entity.get::<Health>().is_ok());
+    assert!(matches!(
+        entity.get_mut::<Health>(),
+        Err(EcsError::StorageLocked(_))
+    ));
+}
+
+#[test]
+fn spawn_rollback() {
+    let world = World::new();
+    let immortal = world.spawn((Health(1.0), Immortal));
+
+    let guard = immortal.get_mut::<Immortal>().unwrap();
+    assert!(matches!(
+        world.try_spawn((Health(2.0), Immortal)),
+        Err(EcsError::StorageLocked(_))
+    ));
+    drop(guard);
+    assert!(!world.components.has_component::<Health>(EntityId(1)));
+
+    // The partially spawned entity has been removed and its ID is reused.
+    let entity = world.try_spawn(Health(3.0)).unwrap();
+    assert_eq!(entity.id(), EntityId(1));
+    assert_eq!(entity.get::<Health>().unwrap().0, 3.0);
+    assert_eq!(immortal.get::<Health>().unwrap().0, 1.0);
+}
+
+#[test]
+fn spawn_batch() {
+    let world = World::new();
+    let first = world.spawn(Health(-1.0));
+    world.spawn(Immortal);
+    first.despawn();
+    world.scheduler.post_tick(&world);
+
+    let ids = world.spawn_batch((0..100).map(|i| (Health(i as f32), Immortal)));
+    assert_eq!(ids.len(), 100);
+    // The gap left by the despawned entity is filled first.
+    assert_eq!(ids[0], EntityId(0));
+    assert_eq!(ids[1], EntityId(2));
+
+    for (i, id) in ids.iter().enumerate() {
+        assert_eq!(world.components.get::<Health>(*id).unwrap().0, i as f32);
+        assert!(world.components.has_component::<Immortal>(*id));
+    }
+
+    let players = world.spawn_batch((0..10).map(|_| Player));
+    for id in &players {
+        assert!(world.components.has_component::<Armor>(*id));
+        assert_eq!(world.components.get::<Health>(*id).unwrap().0, 1.0);
+    }
+
+    let duplicates = (0..10).map(|i| (Health(i as f32), Health(10.0 + i as f32)));
+    let bundle = std::any::type_name::<(Health, Health)>();
+    assert_eq!(
+        world.try_spawn_batch(duplicates),
+        Err(EcsError::DuplicateComponent(bundle))
+    );
+    assert!(matches!(
+        world.try_spawn((Immortal, Immortal)),
+        Err(EcsError::DuplicateComponent(_))
+    ));
+}
+
+#[test]
+fn remove_bundle_and_take() {
+    let world = World::new();
+    let entity = world.spawn((
+        Health(1.0),
+        Immortal,
+        LastUpdate {
+            instant: Instant::now(),
+        },
+    ));
+
+    entity.remove_bundle::<(Health, Immortal)>();
+    assert!(entity.has::<Health>());
+    world.scheduler.post_tick(&world);
+    assert!(!entity.has::<Health>());
+    assert!(!entity.has::<Immortal>());
+    assert!(entity.has::<LastUpdate>());
+
+    let other = world.spawn(Health(2.0));
+    let _guard = other.get::<Health>().unwrap();
+    assert!(world.take::<LastUpdate>(entity.id()).unwrap().is_some());
+    assert!(world.take::<LastUpdate>(entity.id()).unwrap().is_none());
+    assert!(matches!(
+        world.take::<Health>(other.id()),
+        Err(EcsError::StorageLocked(_))
+    ));
+}
+
+#[test]
+fn required_components() {
+    let world = World::new();
+
+    let player = world.spawn(Player);
+    assert_eq!(*player.get::<Position>().unwrap(), Position::origin());
+    assert_eq!(player.get::<Armor>().unwrap().0, 0);
+    // Required transitively through `Armor`.
+    assert_eq!(player.get::<Health>().unwrap().0, 1.0);
+
+    // Explicitly provided components are not overwritten.
+    let player = world.spawn((Player, Health(20.0)));
+    assert_eq!(player.get::<Health>().unwrap().0, 20.0);
+
+    let entity = world.spawn_empty();
+    entity.insert(Player);
+    assert!(!entity.has::<Armor>());
+    world.scheduler.post_tick(&world);
+    assert!(entity.has::<Player>());
+    assert!(entity.has::<Armor>());
+    assert!(entity.has::<Health>());
+}
+
+#[derive(Default)]
+struct XuidIndex(std::collections::HashMap<u64, EntityId>);
+
+impl Resource for XuidIndex {}
+
+#[derive(Component)]
+#[component(on_insert = index_xuid, on_replace = unindex_xuid)]
+struct Xuid(u64);
+
+fn index_xuid(world: DeferredWorld, entity: EntityId) {
+    let xuid = world.get::<Xuid>(entity).unwrap().0;
+    world.resource_mut::<XuidIndex>().0.insert(xuid, entity);
+}
+
+fn unindex_xuid(world: DeferredWorld, entity: EntityId) {
+    let xuid = world.get::<Xuid>(entity).unwrap().0;
+    world.resource_mut::<XuidIndex>().0.remove(&xuid);
+}
+
+#[test]
+fn component_hooks() {
+    let world = World::new();
+    world.add_resource(XuidIndex::default());
+
+    let index = |xuid| {
+        DeferredWorld::new(&world)
+            .resource::<XuidIndex>()
+            .0
+            .get(&xuid)
+            .copied()
+    };
+
+    let first = world.spawn(Xuid(1));
+    let second = world.spawn(Xuid(2));
+    assert_eq!(index(1), Some(first.id()));
+    assert_eq!(index(2), Some(second.id()));
+
+    // Replacing the component unindexes the old value.
+    world.components.insert(first.id(), Xuid(3)).unwrap();
+    assert_eq!(index(1), None);
+    assert_eq!(index(3), Some(first.id()));
+
+    first.remove::<Xuid>();
+    second.despawn();
+    world.scheduler.post_tick(&world);
+    assert_eq!(index(2), None);
+    assert_eq!(index(3), None);
+
+    // Hooks can also be registered at runtime.
+    world.register_hooks::<Health>(|hooks| {
+        hooks.on_add(|world, entity| world.entity(entity).insert(Immortal));
+    });
+    let entity = world.spawn(Health(1.0));
+    world.scheduler.post_tick(&world);
+    assert!(entity.has::<Immortal>());
+
+    // Hooks do not run when the storage is locked and the removal fails.
+    static REMOVED: AtomicUsize = AtomicUsize::new(0);
+    world.register_hooks::<Immortal>(|hooks| {
+        hooks.on_remove(|_, _| {
+            REMOVED.fetch_add(1, Ordering::SeqCst);
+        });
+    });
+    let guard = entity.get::<Immortal>().unwrap();
+    assert!(matches!(
+        world.take::<Immortal>(entity.id()),
+        Err(EcsError::StorageLocked(_))
+    ));
+    assert_eq!(REMOVED.load(Ordering::SeqCst), 0);
+    drop(guard);
+    assert!(world.take::<Immortal>(entity.id()).unwrap().is_some());
+    assert_eq!(REMOVED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn hierarchy() {
+    let world = World::new();
+    let root = world.spawn_empty();
+    let a = world.spawn_empty();
+    let b = world.spawn_empty();
+    let c = world.spawn_empty();
+    let unrelated = world.spawn_empty();
+
+    root.add_child(a.id()).unwrap();
+    root.add_child(b.id()).unwrap();
+    c.set_parent(b.id()).unwrap();
+    world.scheduler.post_tick(&world);
+
+    assert_eq!(root.children().unwrap().as_slice(), &[a.id(), b.id()]);
+    assert_eq!(c.parent().unwrap(), Some(b.id()));
+    assert_eq!(root.descendants().unwrap(), vec![a.id(), b.id(), c.id()]);
+    assert_eq!(c.ancestors().unwrap(), vec![b.id(), root.id()]);
+
+    // Reparenting removes the child from its old parent.
+    c.set_parent(a.id()).unwrap();
+    world.scheduler.post_tick(&world);
+    assert!(!b.has::<Children>());
+    assert_eq!(a.children().unwrap().as_slice(), &[c.id()]);
+
+    // Despawning a child removes it from its parent.
+    b.clone().despawn();
+    world.scheduler.post_tick(&world);
+    assert_eq!(root.children().unwrap().as_slice(), &[a.id()]);
+
+    root.clone().despawn_recursive();
+    world.scheduler.post_tick(&world);
+    for entity in [&root, &a, &c] {
+        assert!(!world.entities.contains(entity.id()));
+        assert!(!entity.has::<Parent>() && !entity.has::<Children>());
+    }
+    assert!(world.entities.contains(unrelated.id()));
+}
+
+#[test]
+fn hierarchy_cycles() {
+    let world = World::new();
+    let a = world.spawn_empty();
+    let b = world.spawn_empty();
+
+    // Both changes are valid on their own, but the second one would close a cycle once the first is applied.
+    a.set_parent(b.id()).unwrap();
+    b.set_parent(a.id()).unwrap();
+    world.scheduler.post_tick(&world);
+    assert_eq!(a.parent().unwrap(), Some(b.id()));
+    assert_eq!(b.parent().unwrap(), None);
+
+    assert_eq!(b.set_parent(a.id()), Err(EcsError::HierarchyCycle));
+    assert_eq!(a.add_child(a.id()), Err(EcsError::HierarchyCycle));
+    world.scheduler.post_tick(&world);
+    assert_eq!(b.parent().unwrap(), None);
+
+    let c = world.spawn_empty();
+    let query = Query::<&mut Parent>::new(&world).unwrap();
+    assert!(matches!(
+        c.set_parent(a.id()),
+        Err(EcsError::StorageLocked(_))
+    ));
+    drop(query);
+    world.scheduler.post_tick(&world);
+    assert_eq!(c.parent().unwrap(), None);
+}
+
+struct Targets {
+    priority: u32,
+}
+
+impl Relation for Targets {}
+
+#[test]
+fn relations() {
+    let world = World::new();
+    let player = world.spawn(Health(20.0));
+    let zombie = world.spawn(Health(10.0));
+    let skeleton = world.spawn((Health(5.0), Immortal));
+
+    zombie.relate(player.id(), Targets { priority: 1 });
+    skeleton.relate(player.id(), Targets { priority: 2 });
+    world.scheduler.post_tick(&world);
+
+    assert_eq!(zombie.related::<Targets>().unwrap(), Some(player.id()));
+    let mut sources = world.related_to::<Targets>(player.id());
+    sources.sort_by_key(|id| id.0);
+    assert_eq!(sources, vec![zombie.id(), skeleton.id()]);
+
+    let query = Query::<&Related<Targets>, Without<Immortal>>::new(&world).unwrap();
+    let priorities: Vec<u32> = query
+        .iter_related_to::<Targets>(player.id())
+        .map(|related| related.relation().priority)
+        .collect();
+    assert_eq!(priorities, vec![1]);
+    drop(query);
+
+    // Despawning the target removes all relations pointing to it.
+    player.despawn();
+    world.scheduler.post_tick(&world);
+    assert!(!zombie.has::<Related<Targets>>());
+    assert!(!skeleton.has::<Related<Targets>>());
+    assert!(world.relations.targets.is_empty());
+}
+
+#[test]
+fn disabled_entities() {
+    let world = World::new();
+    let active = world.spawn(Health(1.0));
+    let loading = world.spawn(Health(2.0));
+
+    loading.disable();
+    assert!(!loading.is_disabled());
+    world.scheduler.post_tick(&world);
+    assert!(loading.is_disabled());
+
+    let count = |world: &Arc<World>| Query::<&Health>::new(world).unwrap().into_iter().count();
+    let count_all = |world: &Arc<World>| {
+        Query::<&Health, WithDisabled>::new(world)
+            .unwrap()
+            .into_iter()
+            .count()
+    };
+
+    assert_eq!(count(&world), 1);
+    assert_eq!(count_all(&world), 2);
+    assert_eq!(loading.get::<Health>().unwrap().0, 2.0);
+
+    // Filtering on `Disabled` implies that disabled entities are visited.
+    let query = Query::<EntityId, With<Disabled>>::new(&world).unwrap();
+    let ids: Vec<EntityId> = query.into_iter().collect();
+    assert_eq!(ids, vec![loading.id()]);
+    drop(query);
+
+    // So does borrowing the `Disabled` component itself.
+    let query = Query::<(EntityId, &Disabled)>::new(&world).unwrap();
+    let ids: Vec<EntityId> = query.into_iter().map(|(id, _)| id).collect();
+    assert_eq!(ids, vec![loading.id()]);
+    drop(query);
+    let count_disabled = Query::<&Disabled>::new(&world).unwrap().into_iter().count();
+    assert_eq!(count_disabled, 1);
+
+    loading.enable();
+    active.disable();
+    world.scheduler.post_tick(&world);
+    let query = Query::<Entity>::new(&world).unwrap();
+    let ids: Vec<EntityId> = query.into_iter().map(|e| e.id()).collect();
+    assert_eq!(ids, vec![loading.id()]);
+}
+
+#[test]
+fn borrowed_entity_handles() {
+    let world = World::new();
+    let first = world.spawn(Health(1.0)).id();
+    let second = world.spawn((Health(2.0), Immortal)).id();
+
+    let query = Query::<(EntityId, &Health)>::new(&world).unwrap();
+    let ids: Vec<EntityId> = query.into_iter().map(|(id, _)| id).collect();
+    assert_eq!(ids, vec![first, second]);
+    drop(query);
+
+    // Iterating borrowed handles does not clone the world.
+    let strong = Arc::strong_count(&world);
+    let query = Query::<EntityRef, With<Immortal>>::new(&world).unwrap();
+    for entity in &query {
+        assert_eq!(Arc::strong_count(&world), strong + 1);
+        assert_eq!(entity.get::<Health>().unwrap().0, 2.0);
+    }
+    drop(query);
+
+    let query = Query::<EntityMut>::new(&world).unwrap();
+    for mut entity in &query {
+        entity.get_mut::<Health>().unwrap().0 += 1.0;
+    }
+    drop(query);
+    assert_eq!(world.entity(first).get::<Health>().unwrap().0, 2.0);
+    assert_eq!(world.entity(second).get::<Health>().unwrap().0, 3.0);
+
+    // `EntityMut` can access any component, so it is scheduled like exclusive world access.
+    assert_eq!(
+        <Query<(EntityId, EntityMut)>>::descriptor(),
+        SystemParamDescriptor::World
+    );
+}
+
+#[derive(Component)]
+struct Owner(Entity);
+
+struct LastKilled(Entity);
+
+impl Resource for LastKilled {}
+
+#[test]
+fn world_shutdown() {
+    let world = World::new();
+    let weak = Arc::downgrade(&world);
+
+    let entity = world.spawn(Health(1.0));
+    world.spawn(Owner(entity.clone()));
+    world.add_resource(LastKilled(entity.clone()));
+    world.events.add_reader::<Killed>();
+    world.events.insert(Killed {
+        entity: entity.clone(),
+    });
+
+    let owner = Query::<&Owner>::new(&world).unwrap();
+    assert_eq!(owner.into_iter().next().unwrap().0.id(), entity.id());
+    drop(owner);
+    let last_killed = DeferredWorld::new(&world).resource::<LastKilled>().0.id();
+    assert_eq!(last_killed, entity.id());
+
+    world.register_hooks::<Health>(|hooks| {
+        hooks.on_add(|world, entity| world.entity(entity).insert(Immortal));
+    });
+
+    // Shutdown is rejected while anything is borrowed.
+    let guard = entity.get::<Health>().unwrap();
+    assert!(matches!(world.shutdown(), Err(EcsError::StorageLocked(_))));
+    drop(guard);
+
+    // Nothing is cleared when only a resource is borrowed.
+    let guard = DeferredWorld::new(&world).resource::<LastKilled>();
+    assert_eq!(guard.0.id(), entity.id());
+    assert!(matches!(world.shutdown(), Err(EcsError::StorageLocked(_))));
+    drop(guard);
+    assert!(entity.has::<Health>());
+
+    world.shutdown().unwrap();
+    assert!(!entity.has::<Health>());
+
+    // Hooks registered at runtime survive the shutdown.
+    let respawned = world.spawn(Health(1.0));
+    world.scheduler.post_tick(&world);
+    assert!(respawned.has::<Immortal>());
+    world.shutdown().unwrap();
+
+    drop(respawned);
+    drop(entity);
+    drop(world);
+    assert!(weak.upgrade().is_none());
+}
+
+#[derive(Default)]
+struct ScheduleLog(Vec<&'static str>);
+
+impl Resource for ScheduleLog {}
+
+fn log_startup(mut log: ResMut<ScheduleLog>) {
+    log.0.push("startup");
+}
+
+fn log_pre_update(query: Query<Entity, Without<Armor>>, mut log: ResMut<ScheduleLog>) {
+    for entity in &query {
+        entity.insert(Armor(1));
+    }
+    log.0.push("pre_update");
+}
+
+fn log_update(query: Query<&Armor>, mut log: ResMut<ScheduleLog>) {
+    // Insertions from `PreUpdate` have been applied before `Update` runs.
+    assert_eq!(query.into_iter().count(), 1);
+    log.0.push("update");
+}
+
+fn log_post_update(mut log: ResMut<ScheduleLog>) {
+    log.0.push("post_update");
+}
+
+fn log_shutdown(mut log: ResMut<ScheduleLog>) {
+    log.0.push("shutdown");
+}
+
+#[tokio::test]
+async fn world_schedules() {
+    let world = World::new();
+    world.add_resource(ScheduleLog::default());
+    world.spawn(Health(1.0));
+
+    // Registered out of order on purpose, schedules always run in the same order.
+    world.add_system(ScheduleLabel::PostUpdate, log_post_update);
+    world.add_system(ScheduleLabel::Update, log_update);
+    world.add_system(ScheduleLabel::Shutdown, log_shutdown);
+    world.add_system(ScheduleLabel::PreUpdate, log_pre_update);
+    world.add_system(ScheduleLabel::Startup, log_startup);
+
+    world.tick().await;
+    world.tick().await;
+
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(
+        log,
+        [
+            "startup",
+            "pre_update",
+            "update",
+            "post_update",
+            "pre_update",
+            "update",
+            "post_update"
+        ]
+    );
+
+    world.run_schedule(ScheduleLabel::Shutdown).await;
+    assert_eq!(
+        DeferredWorld::new(&world)
+            .resource::<ScheduleLog>()
+            .0
+            .last(),
+        Some(&"shutdown")
+    );
+
+    // A panicking system does not leave its schedule stuck in the running state.
+    world.add_system(ScheduleLabel::Update, panic_exclusive);
+    let handle = Arc::clone(&world);
+    let joined =
+        tokio::spawn(async move { handle.run_schedule(ScheduleLabel::Update).await }).await;
+    assert!(joined.unwrap_err().is_panic());
+    assert!(!world.schedule_scope(ScheduleLabel::Update, |schedule| schedule.is_empty()));
+
+    world.exit().await.unwrap();
+    assert!(world.schedule_scope(ScheduleLabel::Update, |schedule| schedule.is_empty()));
+}
+
+fn panic_exclusive(_world: ExclusiveWorld) {
+    panic!("system failed");
+}
+
+fn heal(query: Query<&mut Health>) {
+    for health in &query {
+        health.0 += 1.0;
+    }
+}
+
+fn count_kills(counter: Res<KillCounter>) {
+    assert_eq!(counter.0, 0);
+}
+
+fn reset_kills(mut counter: ResMut<KillCounter>) {
+    counter.0 = 0;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conflicting_systems_are_batched() {
+    let world = World::new();
+    world.add_resource(KillCounter(0));
+    let entity = world.spawn(Health(0.0));
+
+    let mut schedule = world.schedule_multi_threaded();
+    let heal1 = schedule.add_system(heal).id();
+    let count = schedule.add_system(count_kills).id();
+    let heal2 = schedule.add_system(heal).id();
+    let reset = schedule.add_system(reset_kills).id();
+
+    for _ in 0..10 {
+        schedule.run().await;
+    }
+
+    // Both healing systems mutably query `Health` and `ResMut` conflicts with `Res`.
+    assert_eq!(
+        schedule.batches().unwrap(),
+        [vec![heal1, count], vec![heal2, reset]]
+    );
+    assert_eq!(entity.get::<Health>().unwrap().0, 20.0);
+
+    // Panics inside a spawned system are propagated instead of being dropped.
+    schedule.add_system(panic_query);
+    let joined = tokio::spawn(async move { schedule.run().await }).await;
+    assert!(joined.unwrap_err().is_panic());
+
+    world.shutdown().unwrap();
+}
+
+fn panic_query(_query: Query<&Health>) {
+    panic!("system failed");
+}
+
+fn log_first(mut log: ResMut<ScheduleLog>) {
+    log.0.push("first");
+}
+
+async fn log_async(mut log: ResMut<ScheduleLog>) {
+    tokio::task::yield_now().await;
+    log.0.push("async");
+}
+
+fn log_last(mut log: ResMut<ScheduleLog>) {
+    log.0.push("last");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn single_threaded_executor() {
+    let world = World::new();
+    world.add_resource(ScheduleLog::default());
+
+    let mut schedule = world.schedule_single_threaded();
+    schedule.add_system(log_first);
+    schedule.add_async_system(log_async);
+    schedule.add_system(log_last);
+
+    schedule.run().await;
+    schedule.run().await;
+
+    // The async system is awaited before the next system runs.
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log, ["first", "async", "last", "first", "async", "last"]);
+}
+
+fn log_a(mut log: ResMut<ScheduleLog>) {
+    log.0.push("a");
+}
+
+fn log_b(mut log: ResMut<ScheduleLog>) {
+    log.0.push("b");
+}
+
+fn log_c(mut log: ResMut<ScheduleLog>) {
+    log.0.push("c");
+}
+
+fn log_d(mut log: ResMut<ScheduleLog>) {
+    log.0.push("d");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn system_ordering() {
+    let world = World::new();
+    world.add_resource(ScheduleLog::default());
+
+    let mut schedule = world.schedule_multi_threaded();
+    schedule.add_system(log_d).in_set("last");
+    let chain = schedule.add_systems((log_c, log_b, log_a).chain());
+    schedule
+        .add_system(log_first)
+        .before("last")
+        .after(chain[2]);
+    schedule.run().await;
+
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log, ["c", "b", "a", "first", "d"]);
+
+    // c -> b -> d -> c
+    let mut schedule = world.schedule_single_threaded();
+    let d = schedule.add_system(log_d).id();
+    let chain = schedule.add_systems((log_c, log_b).chain());
+    schedule.configure(d).after(chain[1]).before(chain[0]);
+
+    let Err(EcsError::ScheduleCycle(cycle)) = schedule.build() else {
+        panic!("expected an ordering cycle");
+    };
+    assert_eq!(cycle.len(), 3);
+    assert!(cycle.iter().any(|name| name.ends_with("log_d")));
+}
+
+#[derive(Default)]
+struct Paused(bool);
+
+impl Resource for Paused {}
+
+fn not_paused(paused: Res<Paused>) -> bool {
+    !paused.0
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn system_sets() {
+    let world = World::new();
+    world.add_resource(ScheduleLog::default());
+    world.add_resource(Paused(false));
+
+    let mut schedule = world.schedule_multi_threaded();
+    schedule
+        .configure_set("gameplay")
+        .after("input")
+        .run_if(not_paused);
+    schedule.configure_set("combat").in_set("gameplay");
+
+    let d = schedule.add_system(log_d).in_set("combat").id();
+    let c = schedule.add_system(log_c).in_set("gameplay").id();
+    let b = schedule.add_system(log_b).in_set("input").id();
+    schedule.add_system(log_a).in_set("debug");
+
+    assert_eq!(schedule.sets(), ["combat", "debug", "gameplay", "input"]);
+    assert_eq!(schedule.parent_sets("combat"), ["gameplay"]);
+    assert_eq!(schedule.systems_in_set("gameplay"), [d, c]);
+    assert_eq!(schedule.systems_in_set("input"), [b]);
+
+    schedule.run().await;
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log, ["b", "d", "c", "a"]);
+
+    // The run condition of `gameplay` also applies to the nested `combat` set.
+    DeferredWorld::new(&world).resource_mut::<Paused>().0 = true;
+    schedule.configure_set("debug").enabled(false);
+    schedule.run().await;
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log, ["b", "d", "c", "a", "b"]);
+}
+
+#[derive(Debug, PartialEq)]
+enum GameState {
+    Lobby,
+    Playing,
+}
+
+impl Resource for GameState {}
+
+#[tokio::test]
+async fn run_conditions() {
+    let world = World::new();
+    world.add_resource(ScheduleLog::default());
+    world.add_resource(GameState::Lobby);
+
+    let mut schedule = world.schedule_single_threaded();
+    schedule.add_system(log_a).run_if(every_n_ticks(2));
+    schedule
+        .add_system(log_b)
+        .run_if(resource_exists::<Paused>());
+    schedule
+        .add_system(log_c)
+        .run_if(in_state(GameState::Playing));
+    schedule.add_system(log_d).run_if(on_event::<Interval>());
+
+    schedule.run().await;
+    DeferredWorld::new(&world).send_event(Interval);
+    schedule.run().await;
+    world.add_resource(Paused(false));
+    *DeferredWorld::new(&world).resource_mut::<GameState>() = GameState::Playing;
+    schedule.run().await;
+
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log, ["a", "d", "a", "b", "c"]);
+}
+
+fn aliased_health(_a: Query<&mut Health>, _b: Query<&Health>) {}
+
+fn aliased_counter(_a: Res<KillCounter>, _b: ResMut<KillCounter>) {}
+
+fn disjoint_access(_a: Query<(&mut Health, &Armor)>, _b: Query<&Armor>, _c: Res<KillCounter>) {}
+
+#[test]
+fn conflicting_access_is_rejected() {
+    let world = World::new();
+    let mut schedule = world.schedule_single_threaded();
+
+    let Err(EcsError::ConflictingAccess { system, types }) =
+        schedule.try_add_system(aliased_health).map(|c| c.id())
+    else {
+        panic!("expected conflicting access");
+    };
+    assert!(system.ends_with("aliased_health"));
+    assert_eq!(types, [std::any::type_name::<Health>()]);
+
+    let err = schedule
+        .try_add_system(aliased_counter)
+        .map(|c| c.id())
+        .unwrap_err();
+    assert!(err.to_string().contains("KillCounter"));
+
+    schedule.try_add_system(disjoint_access).unwrap();
+    assert_eq!(schedule.len(), 1);
+}
+
+type FollowSet<'a> = ParamSet<(
+    Query<&'a mut Position, With<Player>>,
+    Query<&'a Position, Without<Player>>,
+)>;
+
+fn follow(mut set: FollowSet) {
+    let target = set.p1().into_iter().map(|p| (p.0, p.1)).next().unwrap();
+    for position in &*set.p0() {
+        *position = Position(target.0, target.1);
+    }
+}
+
+fn aliased_param_set(
+    _set: ParamSet<(Query<&mut Position>, Query<&Position>)>,
+    _p: Query<&Position>,
+) {
+}
+
+#[tokio::test]
+async fn param_set() {
+    let world = World::new();
+    let player = world.spawn(Player);
+    world.spawn(Position(3.0, 4.0));
+
+    let mut schedule = world.schedule_single_threaded();
+    schedule.add_system(follow);
+
+    // Conflicts between the parameters in the set are fine, conflicts with the rest of the system are not.
+    let Err(EcsError::ConflictingAccess { types, .. }) =
+        schedule.try_add_system(aliased_param_set).map(|c| c.id())
+    else {
+        panic!("expected conflicting access");
+    };
+    assert_eq!(types, [std::any::type_name::<Position>()]);
+
+    schedule.run().await;
+    assert_eq!(*player.get::<Position>().unwrap(), Position(3.0, 4.0));
+}
+
+struct Tracked {
+    entity: EntityId,
+    has_health: Option<bool>,
+}
+
+impl Resource for Tracked {}
+
+fn strip_health(query: Query<Entity, With<Health>>) {
+    for entity in &query {
+        entity.remove::<Health>();
+    }
+}
+
+fn observe_health(world: ExclusiveWorld) {
+    let mut tracked = world.resource_mut::<Tracked>();
+    tracked.has_health = Some(world.entity(tracked.entity).has::<Health>());
+}
+
+fn exclusive_with_query(_world: ExclusiveWorld, _query: Query<&Health>) {}
+
+#[tokio::test]
+async fn exclusive_systems() {
+    let world = World::new();
+
+    // Exclusive systems see the changes made by the systems that ran before them.
+    let entity = world.spawn(Health(1.0)).id();
+    world.add_resource(Tracked {
+        entity,
+        has_health: None,
+    });
+    let mut schedule = world.schedule_single_threaded();
+    let strip = schedule.add_system(strip_health).id();
+    schedule.add_system(observe_health).after(strip);
+    schedule.run().await;
+    let tracked = DeferredWorld::new(&world).resource::<Tracked>().has_health;
+    assert_eq!(tracked, Some(false));
+
+    let entity = world.spawn(Health(1.0)).id();
+    DeferredWorld::new(&world).resource_mut::<Tracked>().entity = entity;
+    world.add_resource(ScheduleLog::default());
+    let strip = world.add_system(ScheduleLabel::Update, strip_health);
+    world.schedule_scope(ScheduleLabel::Update, |schedule| {
+        let observe = schedule.add_system(observe_health).after(strip).id();
+        schedule.add_system(log_a);
+
+        // The exclusive system runs in a batch by itself.
+        schedule.build().unwrap();
+        let batches = schedule.batches().unwrap();
+        assert!(batches.contains(&vec![observe]));
+    });
+    world.run_schedule(ScheduleLabel::Update).await;
+    let tracked = DeferredWorld::new(&world).resource::<Tracked>().has_health;
+    assert_eq!(tracked, Some(false));
+
+    // Exclusive access cannot be combined with other borrows.
+    let Err(EcsError::ConflictingAccess { types, .. }) = schedule
+        .try_add_system(exclusive_with_query)
+        .map(|c| c.id())
+    else {
+        panic!("expected conflicting access");
+    };
+    assert_eq!(types, [std::any::type_name::<Health>()]);
+}
+
+fn check_player_immortal(query: Query<Entity, With<Player>>) -> EcsResult<()> {
+    for entity in &query {
+        entity.get::<Immortal>()?;
+    }
+    Ok(())
+}
+
+fn parse_player_levels(query: Query<Entity, With<Player>>) -> SystemResult {
+    for entity in &query {
+        let position = entity.get::<Position>()?;
+        // The origin is formatted as `0.0`, which is not a valid level.
+        let _level: u32 = format!("{:?}", position.0).parse()?;
+    }
+    Ok(())
+}
+
+async fn failing_async(_log: Res<ScheduleLog>) -> Result<(), std::fmt::Error> {
+    tokio::task::yield_now().await;
+    Err(std::fmt::Error)
+}
+
+#[tokio::test]
+async fn fallible_systems() {
+    let world = World::new();
+    world.spawn(Player);
+    world.add_resource(ScheduleLog::default());
+
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let mut schedule = world.schedule_multi_threaded();
+    let check = schedule.add_system(check_player_immortal).id();
+    let fail = schedule.add_async_system(failing_async).id();
+    let parse = schedule.add_system(parse_player_levels).id();
+
+    let recorded = Arc::clone(&failures);
+    schedule.set_error_handler(ErrorHandler::custom(move |ctx, err| {
+        let names = [
+            "check_player_immortal",
+            "failing_async",
+            "parse_player_levels",
+        ];
+        assert!(names.iter().any(|name| ctx.name.ends_with(name)));
+        recorded.lock().push((ctx.id, err.to_string()));
+    }));
+    schedule.run().await;
+
+    let mut recorded = failures.lock().clone();
+    recorded.sort();
+    assert_eq!(
+        recorded,
+        [
+            (check, EcsError::NotFound.to_string()),
+            (fail, std::fmt::Error.to_string()),
+            (parse, "0.0".parse::<u32>().unwrap_err().to_string())
+        ]
+    );
+
+    // Disabled systems stop running after their first failure.
+    schedule.set_error_handler(ErrorHandler::Disable);
+    schedule.run().await;
+    schedule.set_error_handler(ErrorHandler::Panic);
+    schedule.run().await;
+}
+
+fn find_wounded(query: Query<(Entity, &Health)>) -> Vec<EntityId> {
+    query
+        .into_iter()
+        .filter(|(_, health)| health.0 < 1.0)
+        .map(|(entity, _)| entity.id())
+        .collect()
+}
+
+fn heal_wounded(In(wounded): In<Vec<EntityId>>, query: Query<(Entity, &mut Health)>) {
+    for (entity, health) in &query {
+        if wounded.contains(&entity.id()) {
+            health.0 = 1.0;
+        }
+    }
+}
+
+#[tokio::test]
+async fn piped_systems() {
+    let world = World::new();
+    let wounded = world.spawn(Health(0.5));
+    world.spawn(Health(1.0));
+
+    // The piped systems conflict with each other, but never run at the same time.
+    let mut schedule = world.schedule_multi_threaded();
+    let pipe = schedule.add_system(find_wounded.pipe(heal_wounded)).id();
+    let other = schedule.add_system(heal).after(pipe).id();
+
+    // The scheduler accounts for the access of both systems.
+    schedule.build().unwrap();
+    let batches = schedule.batches().unwrap();
+    assert!(batches.contains(&vec![pipe]) && batches.contains(&vec![other]));
+
+    schedule.run().await;
+    assert_eq!(wounded.get::<Health>().unwrap().0, 2.0);
+
+    // A system taking piped input cannot be added by itself.
+    let result = schedule.try_add_system(heal_wounded).map(|c| c.id());
+    let Err(EcsError::UnpipedInput(system)) = result else {
+        panic!("expected unpiped input to be rejected");
+    };
+    assert!(system.ends_with("heal_wounded"));
+}
+
+fn count_runs(mut runs: State<usize>, mut log: ResMut<ScheduleLog>) {
+    *runs += 1;
+    log.0.push(if *runs == 1 { "first" } else { "again" });
+}
+
+fn despawn_wounded(query: Query<(Entity, &Health)>) {
+    for (entity, health) in &query {
+        if health.0 < 1.0 {
+            entity.despawn();
+        }
+    }
+}
+
+#[tokio::test]
+async fn one_shot_systems() {
+    let world = World::new();
+    world.add_resource(ScheduleLog::default());
+
+    // Registered systems keep their state between runs.
+    let id = world.register_system(count_runs);
+    world.run_system(id).await.unwrap();
+    world.run_system(id).await.unwrap();
+
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log, ["first", "again"]);
+
+    assert!(world.unregister_system(id));
+    let err = world.run_system(id).await.unwrap_err();
+    assert_eq!(err.to_string(), EcsError::NotFound.to_string());
+
+    // Changes are applied as soon as the system has run.
+    let wounded = world.spawn(Health(0.5)).id();
+    world.run_system_once(despawn_wounded).await.unwrap();
+    assert!(world.entity(wounded).get::<Health>().is_err());
+
+    // Inside a running schedule, the changes are applied by the schedule instead.
+    let wounded = world.spawn(Health(0.5)).id();
+    let mut schedule = world.schedule_single_threaded();
+    schedule.add_async_system(despawn_from_schedule);
+    schedule.run().await;
+    assert!(world.entity(wounded).get::<Health>().is_err());
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log.last(), Some(&"deferred"));
+
+    // A panicking system can run again.
+    let id = world.register_system(panic_query);
+    for _ in 0..2 {
+        let handle = Arc::clone(&world);
+        let joined = tokio::spawn(async move { handle.run_system(id).await }).await;
+        let payload = joined.unwrap_err().into_panic();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"system failed"));
+    }
+
+    // Invalid systems are rejected when they are registered.
+    let Err(EcsError::ConflictingAccess { system, .. }) = world.try_register_system(aliased_health)
+    else {
+        panic!("expected conflicting access");
+    };
+    assert!(system.ends_with("aliased_health"));
+    assert!(matches!(
+        world.try_register_system(heal_wounded),
+        Err(EcsError::UnpipedInput(_))
+    ));
+}
+
+async fn despawn_from_schedule(world: ExclusiveWorld) {
+    world.run_system_once(despawn_wounded).await.unwrap();
+
+    let query = Query::<&Health>::new(&world).unwrap();
+    if query.into_iter().any(|health| health.0 < 1.0) {
+        world.resource_mut::<ScheduleLog>().0.push("deferred");
+    }
+}
+
+fn read_kills(mut reader: EventReader<Killed>, mut log: ResMut<ScheduleLog>) {
+    if reader.read().count() > 0 {
+        log.0.push("killed");
+    }
+}
+
+#[tokio::test]
+async fn remove_and_replace_systems() {
+    let world = World::new();
+    world.add_resource(ScheduleLog::default());
+
+    let mut schedule = world.schedule_single_threaded();
+    let reader = schedule
+        .add_system(read_kills)
+        .run_if(on_event::<Killed>())
+        .id();
+    let other = schedule.add_system(log_a).after(reader).id();
+    // The `on_event` condition does not register a reader of its own.
+    assert_eq!(world.events.reader_count::<Killed>(), 1);
+
+    // Disabled systems are skipped.
+    schedule.set_enabled(other, false);
+    schedule.run().await;
+    assert!(!schedule.is_enabled(other));
+
+    // Replacing a system tears down the old one but keeps its configuration.
+    schedule.replace_system(other, log_b);
+    assert!(schedule.is_enabled(other));
+    assert_eq!(world.events.reader_count::<Killed>(), 1);
+    schedule.run().await;
+
+    // Removing a system unregisters its readers.
+    assert!(schedule.remove_system(reader));
+    assert!(!schedule.remove_system(reader));
+    assert_eq!(world.events.reader_count::<Killed>(), 0);
+    schedule.run().await;
+
+    let log = DeferredWorld::new(&world)
+        .resource::<ScheduleLog>()
+        .0
+        .clone();
+    assert_eq!(log, ["b", "b"]);
+
+    // Dropping a schedule destroys its remaining systems.
+    let mut detached = world.schedule_multi_threaded();
+    detached.add_system(read_kills);
+    assert_eq!(world.events.reader_count::<Killed>(), 1);
+    drop(detached);
+    assert_eq!(world.events.reader_count::<Killed>(), 0);
+}
+
+struct Progress(usize);
+
+impl Resource for Progress {}
+
+async fn make_progress(bg: Background<ResMut<Progress>>) {
+    for _ in 0..2 {
+        bg.access(|progress| progress.0 += 1);
+        bg.next_tick().await;
+    }
+}
+
+async fn wait_forever(bg: Background<Res<Progress>>) {
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    bg.access(|_progress| unreachable!());
+}
+
+fn reset_progress(mut progress: ResMut<Progress>) {
+    if progress.0 == 2 {
+        progress.0 = 10;
+    }
+}
+
+async fn total_health(bg: Background<(Query<&Health>, ResMut<Progress>)>) {
+    bg.access(|(query, progress)| {
+        progress.0 = query.into_iter().map(|health| health.0 as usize).sum();
+    });
+}
+
+#[tokio::test]
+async fn background_systems() {
+    let world = World::new();
+    world.add_resource(Progress(0));
+
+    // The background systems conflict with the regular one, which is fine since they release their
+    // parameters before suspending.
+    let mut schedule = world.schedule_multi_threaded();
+    let progress = schedule.add_background_system(make_progress).id();
+    schedule.add_background_system(wait_forever);
+    schedule.add_system(reset_progress).after(progress);
+
+    let start = Instant::now();
+    let mut observed = Vec::new();
+    for _ in 0..4 {
+        schedule.run().await;
+        observed.push(DeferredWorld::new(&world).resource::<Progress>().0);
+    }
+
+    // The future spans two runs, completes in the third and is started again in the fourth.
+    assert_eq!(observed, [1, 10, 10, 11]);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // Queries can be accessed with elided lifetimes as well.
+    world.spawn(Health(2.0));
+    world.spawn(Health(3.0));
+    let mut schedule = world.schedule_single_threaded();
+    schedule.add_background_system(total_health);
+    schedule.run().await;
+    assert_eq!(DeferredWorld::new(&world).resource::<Progress>().0, 5);
 }
@@ -56,14 +56,27 @@ impl PersistentLock {
     }
 
     pub fn read(&self) -> EcsResult<LockGuard<ReadLock>> {
-        if self.counter.load(Ordering::SeqCst) == usize::MAX {
-            // Lock is already being used for writing.
-            return Err(EcsError::StorageLocked(
-                "write lock active, cannot acquire read lock",
-            ));
+        // Use a compare-exchange loop so that a writer cannot sneak in between the check and the increment.
+        let mut current = self.counter.load(Ordering::SeqCst);
+        loop {
+            if current == usize::MAX {
+                // Lock is already being used for writing.
+                return Err(EcsError::StorageLocked(
+                    "write lock active, cannot acquire read lock",
+                ));
+            }
+
+            match self.counter.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
         }
 
-        self.counter.fetch_add(1, Ordering::SeqCst);
         Ok(LockGuard {
             lock: self,
             _marker: PhantomData,
@@ -75,14 +88,17 @@ impl PersistentLock {
     }
 
     pub fn write(&self) -> EcsResult<LockGuard<WriteLock>> {
-        if self.counter.load(Ordering::SeqCst) != 0 {
+        if self
+            .counter
+            .compare_exchange(0, usize::MAX, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
             // Lock is already being used for reading.
             return Err(EcsError::StorageLocked(
                 "read or write lock active, cannot acquire write lock",
             ));
         }
 
-        self.counter.store(usize::MAX, Ordering::SeqCst);
         Ok(LockGuard {
             lock: self,
             _marker: PhantomData,
@@ -1,28 +1,195 @@
-use crate::component::{Components, SpawnBundle};
-use crate::entity::{Entities, Entity};
-use crate::scheduler::{MultiThreadedExecutor, Schedule, Scheduler, SingleThreadedExecutor};
-use crate::{Events, Resource, Resources, Systems};
-use std::sync::Arc;
+use crate::component::{ComponentHooks, Components, SpawnBundle};
+use crate::entity::{Entities, Entity, EntityId};
+use crate::relation::Relations;
+use crate::scheduler::{
+    MultiThreadedExecutor, OneShotSystemId, OneShotSystems, Schedule, ScheduleLabel, Scheduler,
+    Schedules, SingleThreadedExecutor, SystemId, SystemParamDescriptor,
+};
+use crate::{
+    sealed, AsyncSystem, Background, Component, ComponentMut, ComponentRef, EcsResult, Event,
+    EventId, Events, Fallible, FetchParams, FnContainer, ParameterizedSystem, Res, ResMut,
+    Resource, Resources, System, SystemParam, SystemParams, SystemResult, SystemReturnable,
+};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Weak};
 
-#[derive(Default)]
 pub struct World {
     pub(crate) entities: Entities,
     pub(crate) components: Components,
-    pub(crate) systems: Systems,
+    pub(crate) schedules: Schedules,
+    pub(crate) systems: OneShotSystems,
     pub(crate) scheduler: Scheduler,
     pub(crate) events: Events,
     pub(crate) resources: Resources,
+    pub(crate) relations: Relations,
 }
 
 impl World {
     pub fn new() -> Arc<World> {
-        Arc::new(World::default())
+        // There is no `Default` implementation since hooks and schedules need a handle to the world,
+        // which only exists once it has been placed in an `Arc`.
+        Arc::new_cyclic(|weak| {
+            let mut world = World {
+                entities: Entities::new(),
+                components: Components::default(),
+                schedules: Schedules::default(),
+                systems: OneShotSystems::default(),
+                scheduler: Scheduler::default(),
+                events: Events::default(),
+                resources: Resources::default(),
+                relations: Relations::default(),
+            };
+            world.components.world = Weak::clone(weak);
+            world.schedules.world = Weak::clone(weak);
+            world
+        })
     }
 
+    /// Spawns a new entity with the given components.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the component storages is currently locked or if the bundle contains
+    /// the same component type twice. See [`try_spawn`](Self::try_spawn) for a fallible version.
     pub fn spawn<B: SpawnBundle>(self: &Arc<Self>, bundle: B) -> Entity {
+        self.try_spawn(bundle).expect("Cannot spawn entity")
+    }
+
+    /// Spawns a new entity with the given components.
+    ///
+    /// Spawning is atomic: either every component in the bundle is inserted or none of them are.
+    /// When an insertion fails, the components inserted so far are removed again and the entity ID is freed.
+    /// Bundles containing the same component type twice are rejected with [`EcsError::DuplicateComponent`](crate::EcsError::DuplicateComponent).
+    pub fn try_spawn<B: SpawnBundle>(self: &Arc<Self>, bundle: B) -> EcsResult<Entity> {
+        B::validate()?;
+
         let entity = self.entities.alloc();
-        bundle.insert_into(&self.components, entity);
+        if let Err(err) = bundle.insert_into(&self.components, entity) {
+            self.rollback_spawn(entity);
+            return Err(err);
+        }
+
+        Ok(Entity {
+            world: Arc::clone(self),
+            id: entity,
+        })
+    }
+
+    /// Spawns a batch of entities, returning their IDs in the same order as the bundles.
+    ///
+    /// Unlike calling [`spawn`](Self::spawn) in a loop, entity IDs are reserved in bulk and every
+    /// component storage is only locked and grown once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the component storages is currently locked or if the bundle contains
+    /// the same component type twice. See [`try_spawn_batch`](Self::try_spawn_batch) for a fallible version.
+    pub fn spawn_batch<B, I>(self: &Arc<Self>, bundles: I) -> Vec<EntityId>
+    where
+        B: SpawnBundle,
+        I: IntoIterator<Item = B>,
+    {
+        self.try_spawn_batch(bundles)
+            .expect("Cannot spawn entities")
+    }
+
+    /// Spawns a batch of entities, returning their IDs in the same order as the bundles.
+    ///
+    /// Like [`try_spawn`](Self::try_spawn), this is atomic: if any insertion fails, none of the entities are spawned.
+    pub fn try_spawn_batch<B, I>(self: &Arc<Self>, bundles: I) -> EcsResult<Vec<EntityId>>
+    where
+        B: SpawnBundle,
+        I: IntoIterator<Item = B>,
+    {
+        B::validate()?;
+
+        let bundles: Vec<B> = bundles.into_iter().collect();
+        let entities = self.entities.alloc_many(bundles.len());
+
+        if let Err(err) = B::insert_batch(bundles, &self.components, &entities) {
+            for entity in &entities {
+                self.rollback_spawn(*entity);
+            }
+
+            return Err(err);
+        }
+
+        Ok(entities)
+    }
+
+    /// Removes the components of an entity that failed to spawn and frees its ID.
+    fn rollback_spawn(&self, entity: EntityId) {
+        if self.components.try_despawn(entity).is_ok() {
+            self.entities.free(entity);
+        } else {
+            // Some of the already inserted components are locked as well.
+            // Let the post-tick despawn clean up whatever is left.
+            self.scheduler.schedule_despawn(entity);
+        }
+    }
+
+    /// Immediately removes a component from an entity and hands back ownership of it.
+    ///
+    /// This returns `Ok(None)` if the entity did not have the component and
+    /// [`EcsError::StorageLocked`](crate::EcsError::StorageLocked) if the storage is currently borrowed,
+    /// for example by a running system.
+    pub fn take<T: Component>(&self, entity: EntityId) -> EcsResult<Option<T>> {
+        self.components.take(entity)
+    }
+
+    /// Registers lifecycle hooks for the component `T`.
+    ///
+    /// The closure receives the currently registered hooks, including the ones declared through the derive macro.
+    ///
+    /// ```ignore
+    /// world.register_hooks::<Xuid>(|hooks| {
+    ///     hooks.on_add(index_xuid).on_remove(unindex_xuid);
+    /// });
+    /// ```
+    pub fn register_hooks<T: Component>(&self, f: impl FnOnce(&mut ComponentHooks)) {
+        self.components.register_hooks::<T>(f)
+    }
+
+    /// Tears down the world, dropping all entities, components, resources, events and pending changes.
+    ///
+    /// [`Entity`] handles hold a strong reference to the world. When they are stored inside the world itself,
+    /// for example in an event, a resource or a component, the world can never be dropped. Shutting the world down
+    /// breaks these cycles, allowing it to be freed once all outside handles are gone. Component hooks are not run,
+    /// but remain registered.
+    ///
+    /// The world remains usable afterwards, but is completely empty.
+    /// This returns [`EcsError::StorageLocked`](crate::EcsError::StorageLocked) without changing anything if any
+    /// component storage or resource is currently borrowed. It must not be called while systems are running.
+    pub fn shutdown(self: &Arc<Self>) -> EcsResult<()> {
+        // Lock everything first so that nothing is cleared unless everything can be.
+        self.components.lock_all()?;
+        if let Err(err) = self.resources.lock_all() {
+            // Safety: The storages have been locked above.
+            unsafe { self.components.unlock_all() };
+            return Err(err);
+        }
+
+        // Safety: Both the storages and the resources have been locked above.
+        unsafe {
+            self.components.clear_locked();
+            self.resources.clear_locked();
+        }
 
+        self.events.clear();
+        self.scheduler.clear();
+        self.schedules.clear(self);
+        self.systems.clear(self);
+        self.relations.clear();
+        self.entities.clear();
+
+        Ok(())
+    }
+
+    /// Returns a handle to the entity with the given ID.
+    pub fn entity(self: &Arc<Self>, entity: EntityId) -> Entity {
         Entity {
             world: Arc::clone(self),
             id: entity,
@@ -46,40 +213,326 @@ impl World {
         self.resources.insert(resource)
     }
 
-    // pub fn add_system<P, R, S>(self: &Arc<Self>, system: S)
-    // where
-    //     P: SystemParams + Send + Sync + 'static,
-    //     R: SystemReturnable + Send + Sync + 'static,
-    //     S: ParameterizedSystem<P, R> + Send + Sync + 'static,
-    //     FnContainer<P, R, S>: System,
-    // {
-    //     let contained = Arc::new(system.into_container(self));
-    //     self.systems.push(self, contained);
-    // }
-
-    // pub fn add_async_system<P, S>(self: &Arc<Self>, system: S)
-    // where
-    //     P: SystemParams + Send + Sync + 'static,
-    //     S: AsyncSystem<P>
-    // {
-    //     let contained = Arc::new(system.pinned(self));
-    //     self.systems.push(self, contained);
-    // }
-
-    // pub fn async_system<P, R, S, F>(self: &Arc<Self>, system: S)
-    // where
-    //     P: SystemParams + Send + Sync + 'static,
-    //     R: SystemReturnable + Send + Sync + 'static,
-    //     S: ParameterizedSystem<P, R> + Send + Sync + 'static,
-    //     AsyncContainer<P, R, S>: System,
-    // {
-    //     let wrapped = AsyncContainer::new(self);
-    //     self.systems.push(self, wrapped);
-    // }
+    /// Adds a system to the schedule with the given label.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule is currently running.
+    pub fn add_system<P, R, S>(&self, label: ScheduleLabel, system: S) -> SystemId
+    where
+        P: SystemParams + 'static,
+        R: SystemReturnable + 'static,
+        S: ParameterizedSystem<P, R> + 'static,
+        FnContainer<P, R, S>: System,
+    {
+        self.schedules
+            .scope(label, |schedule| schedule.add_system(system).id())
+    }
+
+    /// Adds an async system to the schedule with the given label.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule is currently running.
+    pub fn add_async_system<P, S>(&self, label: ScheduleLabel, system: S) -> SystemId
+    where
+        P: SystemParams + 'static,
+        S: AsyncSystem<P>,
+    {
+        self.schedules
+            .scope(label, |schedule| schedule.add_async_system(system).id())
+    }
+
+    /// Adds a background system to the schedule with the given label, see [`Schedule::add_background_system`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule is currently running.
+    pub fn add_background_system<P, S, Fut>(&self, label: ScheduleLabel, system: S) -> SystemId
+    where
+        P: FetchParams + 'static,
+        S: Fn(Background<P>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Fallible,
+    {
+        self.schedules.scope(label, |schedule| {
+            schedule.add_background_system(system).id()
+        })
+    }
+
+    /// Gives mutable access to the schedule with the given label.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule is currently running.
+    pub fn schedule_scope<T>(
+        &self,
+        label: ScheduleLabel,
+        f: impl FnOnce(&mut Schedule<MultiThreadedExecutor>) -> T,
+    ) -> T {
+        self.schedules.scope(label, f)
+    }
 
+    /// Runs a single schedule, applying all changes scheduled by its systems afterwards.
+    pub async fn run_schedule(self: &Arc<Self>, label: ScheduleLabel) {
+        self.schedules.run(label).await;
+    }
+
+    /// Runs one tick of the world.
+    ///
+    /// On the first tick, the [`Startup`](ScheduleLabel::Startup) schedule runs first. Then the
+    /// [`PreUpdate`](ScheduleLabel::PreUpdate), [`Update`](ScheduleLabel::Update) and
+    /// [`PostUpdate`](ScheduleLabel::PostUpdate) schedules run in that order.
+    /// Changes such as spawns, insertions and despawns are applied after each schedule,
+    /// so every schedule observes the changes made by the ones before it.
     pub async fn tick(self: &Arc<Self>) {
-        self.scheduler.pre_tick(self);
-        self.systems.call(self).await;
-        self.scheduler.post_tick(self);
+        if self.schedules.start() {
+            self.run_schedule(ScheduleLabel::Startup).await;
+        }
+
+        for label in ScheduleLabel::TICK {
+            self.run_schedule(label).await;
+        }
+    }
+
+    /// Runs the [`Shutdown`](ScheduleLabel::Shutdown) schedule and then [shuts down](Self::shutdown) the world.
+    pub async fn exit(self: &Arc<Self>) -> EcsResult<()> {
+        self.run_schedule(ScheduleLabel::Shutdown).await;
+        self.shutdown()
+    }
+
+    /// Registers a system that does not run every tick, but only when requested with [`run_system`](Self::run_system).
+    ///
+    /// The system keeps its parameter state, such as [`State`](crate::State), between runs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system borrows the same data in conflicting ways. See [`try_register_system`](Self::try_register_system)
+    /// for a fallible version.
+    pub fn register_system<P, R, S>(self: &Arc<Self>, system: S) -> OneShotSystemId
+    where
+        P: SystemParams + 'static,
+        R: SystemReturnable + 'static,
+        S: ParameterizedSystem<P, R> + 'static,
+        FnContainer<P, R, S>: System,
+    {
+        match self.try_register_system(system) {
+            Ok(id) => id,
+            Err(err) => panic!("Cannot register system: {err}"),
+        }
+    }
+
+    /// Registers a system that only runs when requested, see [`register_system`](Self::register_system).
+    ///
+    /// Like [`Schedule::try_add_system`], this returns an error if the system borrows the same data in
+    /// conflicting ways or takes piped input outside of a pipe.
+    pub fn try_register_system<P, R, S>(self: &Arc<Self>, system: S) -> EcsResult<OneShotSystemId>
+    where
+        P: SystemParams + 'static,
+        R: SystemReturnable + 'static,
+        S: ParameterizedSystem<P, R> + 'static,
+        FnContainer<P, R, S>: System,
+    {
+        let contained = Arc::new(system.into_container(self.systems.next_id(), self));
+        self.systems.register(self, contained)
+    }
+
+    /// Registers an async system, see [`register_system`](Self::register_system).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system borrows the same data in conflicting ways. See [`try_register_async_system`](Self::try_register_async_system)
+    /// for a fallible version.
+    pub fn register_async_system<P, S>(self: &Arc<Self>, system: S) -> OneShotSystemId
+    where
+        P: SystemParams + 'static,
+        S: AsyncSystem<P>,
+    {
+        match self.try_register_async_system(system) {
+            Ok(id) => id,
+            Err(err) => panic!("Cannot register system: {err}"),
+        }
+    }
+
+    /// Registers an async system, see [`try_register_system`](Self::try_register_system).
+    pub fn try_register_async_system<P, S>(
+        self: &Arc<Self>,
+        system: S,
+    ) -> EcsResult<OneShotSystemId>
+    where
+        P: SystemParams + 'static,
+        S: AsyncSystem<P>,
+    {
+        let contained = Arc::new(system.pinned(self.systems.next_id(), self));
+        self.systems.register(self, contained)
+    }
+
+    /// Runs a system registered with [`register_system`](Self::register_system) once,
+    /// applying all changes scheduled by it afterwards.
+    ///
+    /// When called while a schedule is running, for example from within one of its systems,
+    /// the changes are not applied right away but together with those of the schedule once it completes.
+    ///
+    /// Returns the error returned by the system, or [`EcsError::NotFound`](crate::EcsError::NotFound)
+    /// if no system with this ID is registered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system is already running.
+    pub async fn run_system(self: &Arc<Self>, id: OneShotSystemId) -> SystemResult {
+        self.systems.run(self, id).await
+    }
+
+    /// Unregisters a system, returning whether it was registered.
+    pub fn unregister_system(self: &Arc<Self>, id: OneShotSystemId) -> bool {
+        self.systems.unregister(self, id)
+    }
+
+    /// Runs a system a single time without keeping it around, which is useful in tests and scripts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system borrows the same data in conflicting ways.
+    pub async fn run_system_once<P, R, S>(self: &Arc<Self>, system: S) -> SystemResult
+    where
+        P: SystemParams + 'static,
+        R: SystemReturnable + 'static,
+        S: ParameterizedSystem<P, R> + 'static,
+        FnContainer<P, R, S>: System,
+    {
+        let id = self.register_system(system);
+        let result = self.run_system(id).await;
+        self.unregister_system(id);
+
+        result
+    }
+}
+
+/// Restricted access to the world, given to component hooks.
+///
+/// Components and resources can be accessed directly, but structural changes such as
+/// inserting components or despawning entities are deferred to the end of the tick.
+pub struct DeferredWorld<'w> {
+    world: &'w Arc<World>,
+}
+
+impl<'w> DeferredWorld<'w> {
+    pub(crate) fn new(world: &'w Arc<World>) -> Self {
+        Self { world }
+    }
+
+    /// Returns a handle to the given entity.
+    pub fn entity(&self, entity: EntityId) -> Entity {
+        self.world.entity(entity)
+    }
+
+    /// Obtains a shared reference to a component, see [`Entity::get`].
+    pub fn get<T: Component>(&self, entity: EntityId) -> EcsResult<ComponentRef<'w, T>> {
+        self.world.components.get(entity)
+    }
+
+    /// Obtains a unique reference to a component, see [`Entity::get_mut`].
+    pub fn get_mut<T: Component>(&self, entity: EntityId) -> EcsResult<ComponentMut<'w, T>> {
+        self.world.components.get_mut(entity)
+    }
+
+    /// Accesses a resource. The resource is locked on first access.
+    pub fn resource<R: Resource>(&self) -> Res<R> {
+        Res {
+            locked: AtomicBool::new(false),
+            world: Arc::clone(self.world),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Mutably accesses a resource. The resource is locked on first access.
+    pub fn resource_mut<R: Resource>(&self) -> ResMut<R> {
+        ResMut {
+            locked: AtomicBool::new(false),
+            world: Arc::clone(self.world),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The world this handle gives access to.
+    pub(crate) fn world(&self) -> &'w Arc<World> {
+        self.world
+    }
+
+    /// Schedules a command that is applied to the world at the end of the tick.
+    pub(crate) fn schedule_command<F>(&self, command: F)
+    where
+        F: FnOnce(&Arc<World>) + Send + 'static,
+    {
+        self.world.scheduler.schedule_command(command);
+    }
+
+    /// Writes an event, returning its ID.
+    pub fn send_event<E: Event>(&self, event: E) -> EventId {
+        self.world.events.insert(event)
+    }
+}
+
+/// Gives a system access to the entire world.
+///
+/// Systems taking this parameter are exclusive: the executor runs them by themselves, with no other
+/// systems in flight, and applies all pending commands right before they run.
+///
+/// This exclusivity is a scheduling convention rather than a borrow. The world is shared through an [`Arc`],
+/// so this dereferences to `Arc<World>` and never hands out a `&mut World`. Components and resources are still
+/// accessed through their regular locks, and code outside of the schedule, such as spawned tasks or other
+/// schedules running at the same time, can still access the world while an exclusive system runs.
+pub struct ExclusiveWorld {
+    world: Arc<World>,
+}
+
+impl ExclusiveWorld {
+    /// Applies all commands that have been scheduled since the system started running.
+    pub fn flush(&self) {
+        self.world.scheduler.post_tick(&self.world);
+    }
+
+    /// Accesses a resource. The resource is locked on first access.
+    pub fn resource<R: Resource>(&self) -> Res<R> {
+        Res {
+            locked: AtomicBool::new(false),
+            world: Arc::clone(&self.world),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Mutably accesses a resource. The resource is locked on first access.
+    pub fn resource_mut<R: Resource>(&self) -> ResMut<R> {
+        ResMut {
+            locked: AtomicBool::new(false),
+            world: Arc::clone(&self.world),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Deref for ExclusiveWorld {
+    type Target = Arc<World>;
+
+    fn deref(&self) -> &Arc<World> {
+        &self.world
+    }
+}
+
+impl SystemParam for ExclusiveWorld {
+    type State = ();
+
+    fn descriptor() -> SystemParamDescriptor {
+        SystemParamDescriptor::World
+    }
+
+    fn fetch<S: sealed::Sealed>(world: &Arc<World>, _state: &Arc<Self::State>) -> Self {
+        ExclusiveWorld {
+            world: Arc::clone(world),
+        }
+    }
+
+    fn state(_world: &Arc<World>) -> Arc<Self::State> {
+        Arc::new(())
     }
 }
@@ -1,30 +1,127 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
+    parenthesized,
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
-    token, Attribute, ExprStruct, Field, Ident, ItemStruct, Path, Token,
+    spanned::Spanned,
+    DeriveInput, Expr, Ident, Path, Token,
 };
 
-#[proc_macro_derive(Component)]
+/// A component required by the derived component, optionally with an initial value.
+///
+/// Parsed from `Type` or `Type = expr`. A path or closure `expr` is called to construct the component,
+/// any other expression is evaluated every time the component is inserted.
+struct Requirement {
+    ty: Path,
+    value: Option<Expr>,
+}
+
+impl Parse for Requirement {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ty = input.parse()?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
+        Ok(Requirement { ty, value })
+    }
+}
+
+/// The lifecycle hooks that can be declared with `#[component(hook = path)]`.
+const HOOKS: [&str; 4] = ["on_add", "on_insert", "on_replace", "on_remove"];
+
+#[proc_macro_derive(Component, attributes(component))]
 pub fn derive_component(input: TokenStream) -> TokenStream {
-    let input = syn::parse_macro_input!(input as ItemStruct);
+    let input = syn::parse_macro_input!(input as DeriveInput);
 
-    let ItemStruct {
-        attrs,
-        vis,
-        ident,
-        fields,
-        ..
-    } = input;
+    let mut requirements = Vec::new();
+    let mut hooks = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("component"))
+    {
+        let result = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("requires") {
+                let content;
+                parenthesized!(content in meta.input);
+                requirements.extend(Punctuated::<Requirement, Token![,]>::parse_terminated(
+                    &content,
+                )?);
 
-    let expanded = quote! {
-        // #(#attrs)*
-        // #vis struct #ident {
-        //     #fields
-        // }
+                Ok(())
+            } else if let Some(hook) = HOOKS.iter().find(|hook| meta.path.is_ident(hook)) {
+                let ident = Ident::new(hook, meta.path.span());
+                let path: Path = meta.value()?.parse()?;
+                hooks.push(quote! { hooks.#ident(#path); });
+
+                Ok(())
+            } else {
+                Err(meta.error(
+                    "unsupported component attribute, expected `requires`, `on_add`, `on_insert`, `on_replace` or `on_remove`",
+                ))
+            }
+        });
+
+        if let Err(err) = result {
+            return err.to_compile_error().into();
+        }
+    }
 
-        impl Component for #ident {}
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let insert_required = if requirements.is_empty() {
+        quote! {}
+    } else {
+        let ctors: Vec<_> = requirements
+            .iter()
+            .map(|Requirement { ty, value }| match value {
+                Some(value @ (Expr::Path(_) | Expr::Closure(_))) => quote! { #value },
+                Some(value) => quote! { || #value },
+                None => quote! { <#ty as ::std::default::Default>::default },
+            })
+            .collect();
+        let types: Vec<_> = requirements.iter().map(|r| &r.ty).collect();
+
+        quote! {
+            fn insert_required(
+                components: &::ecs::Components,
+                entity: ::ecs::EntityId,
+            ) -> ::ecs::EcsResult<()> {
+                #(components.insert_if_missing::<#types, _>(entity, #ctors)?;)*
+                ::std::result::Result::Ok(())
+            }
+
+            fn insert_required_batch(
+                components: &::ecs::Components,
+                entities: &[::ecs::EntityId],
+            ) -> ::ecs::EcsResult<()> {
+                #(components.insert_batch_if_missing::<#types, _>(entities, #ctors)?;)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    };
+
+    let register_hooks = if hooks.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            fn register_hooks(hooks: &mut ::ecs::ComponentHooks) {
+                #(#hooks)*
+            }
+        }
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::ecs::Component for #ident #ty_generics #where_clause {
+            #insert_required
+            #register_hooks
+        }
     };
 
     TokenStream::from(expanded)